@@ -0,0 +1,47 @@
+/* C interface to the bitify converter. Link against the shared library
+ * built by `cargo build --release --lib --features ffi` (libbitify.so / bitify.dll). */
+
+#ifndef BITIFY_H
+#define BITIFY_H
+
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+#define BITIFY_FORMAT_PNG  0
+#define BITIFY_FORMAT_TEXT 1
+#define BITIFY_FORMAT_ANSI 2
+#define BITIFY_FORMAT_HTML 3
+
+typedef struct {
+    uint32_t density;     /* 0 low, 1 medium, 2 high, 3 ultra, 4 extreme */
+    uint32_t width;       /* characters per row; 0 = the density's default */
+    uint32_t color;       /* 0 truecolor, 1 256-color, 2 mono */
+    bool invert;          /* dark glyphs on white */
+    uint32_t format;      /* BITIFY_FORMAT_* */
+    const char *charset;  /* UTF-8 ramp, darkest first; NULL = density ramp */
+} bitify_options;
+
+/* Either data/len or error (NUL-terminated) is set, never both. */
+typedef struct {
+    uint8_t *data;
+    size_t len;
+    char *error;
+} bitify_buffer;
+
+bitify_options bitify_default_options(void);
+
+/* Converts encoded image bytes; opts may be NULL for the defaults. */
+bitify_buffer bitify_convert(const uint8_t *bytes, size_t len, const bitify_options *opts);
+
+void bitify_buffer_free(bitify_buffer buffer);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
@@ -0,0 +1,367 @@
+use crate::palette::{ColorMode, ansi256_to_rgb, rgb_to_ansi256};
+use crate::{AsciiGrid, AsciiPixel};
+use std::fmt::Write as _;
+use std::io::{self, Write};
+
+const ESC: char = '\x1b';
+const TAB_WIDTH: usize = 8;
+/// Width of the DOS terminals `.ans` art was drawn for.
+pub const DOS_COLUMNS: usize = 80;
+const SAUCE_SIZE: usize = 128;
+const RESET: &str = "\x1b[0m";
+
+/// Default `tolerance` for `AnsiWriter`: small enough that merged runs are
+/// indistinguishable, large enough to shrink photo output considerably.
+pub const DEFAULT_COLOR_TOLERANCE: u8 = 8;
+
+/// Upper half of code page 437, used by most `.ans` art from DOS-era tools.
+const CP437_HIGH: &str = "ÇüéâäàåçêëèïîìÄÅÉæÆôöòûùÿÖÜ¢£¥₧ƒáíóúñÑªº¿⌐¬½¼¡«»░▒▓│┤╡╢╖╕╣║╗╝╜╛┐└┴┬├─┼╞╟╚╔╩╦╠═╬╧╨╤╥╙╘╒╓╫╪┘┌█▄▌▐▀αßΓπΣσµτΦΘΩδ∞φε∩≡±≥≤⌠⌡÷≈°∙·√ⁿ²■\u{a0}";
+
+/// Decodes UTF-8, falling back to CP437 for files that are not valid UTF-8.
+/// Anything after a DOS end-of-file marker (e.g. a SAUCE record) is dropped.
+pub fn decode(bytes: &[u8]) -> String {
+    let bytes = bytes.split(|&b| b == 0x1a).next().unwrap_or_default();
+    match std::str::from_utf8(bytes) {
+        Ok(text) => text.to_string(),
+        Err(_) => bytes
+            .iter()
+            .map(|&b| if b < 0x80 { b as char } else { CP437_HIGH.chars().nth(b as usize - 0x80).unwrap_or('?') })
+            .collect(),
+    }
+}
+
+/// Width in characters recorded in a trailing SAUCE record, for files
+/// whose data type is character-based text.
+pub fn sauce_width(bytes: &[u8]) -> Option<usize> {
+    let record = bytes.get(bytes.len().checked_sub(SAUCE_SIZE)?..)?;
+    if !record.starts_with(b"SAUCE00") || record[94] != 1 {
+        return None;
+    }
+    let width = u16::from_le_bytes([record[96], record[97]]) as usize;
+    (width > 0).then_some(width)
+}
+
+/// The column `parse` should wrap at: the SAUCE width, or the DOS terminal
+/// width for files that are not UTF-8 (and so were decoded as CP437). Plain
+/// UTF-8 text, including bitify's own output, is not wrapped.
+pub fn art_columns(bytes: &[u8]) -> Option<usize> {
+    sauce_width(bytes).or_else(|| std::str::from_utf8(bytes).is_err().then_some(DOS_COLUMNS))
+}
+
+/// The SGR foreground state. Bold turns the eight basic colors into their
+/// bright versions, as on DOS and most terminals, so it is kept apart from
+/// the color it modifies.
+struct Pen {
+    foreground: Foreground,
+    bold: bool,
+}
+
+enum Foreground {
+    Default,
+    Basic(u8),
+    Rgb((u8, u8, u8)),
+}
+
+impl Pen {
+    fn color(&self, default: (u8, u8, u8)) -> (u8, u8, u8) {
+        match self.foreground {
+            Foreground::Default => default,
+            Foreground::Basic(index) if self.bold && index < 8 => ansi256_to_rgb(index + 8),
+            Foreground::Basic(index) => ansi256_to_rgb(index),
+            Foreground::Rgb(color) => color,
+        }
+    }
+
+    /// Applies one SGR sequence (the text between `ESC [` and `m`).
+    /// Background colors and other attributes are skipped.
+    fn apply_sgr(&mut self, params: &str) {
+        let codes: Vec<u32> = params.split(';').map(|code| code.parse().unwrap_or(0)).collect();
+        let mut i = 0;
+        while i < codes.len() {
+            match codes[i] {
+                0 => *self = Pen { foreground: Foreground::Default, bold: false },
+                1 => self.bold = true,
+                22 => self.bold = false,
+                39 => self.foreground = Foreground::Default,
+                code @ 30..=37 => self.foreground = Foreground::Basic((code - 30) as u8),
+                code @ 90..=97 => self.foreground = Foreground::Basic((code - 90 + 8) as u8),
+                extended @ (38 | 48) => {
+                    let (value, used) = match codes.get(i + 1) {
+                        Some(5) => (codes.get(i + 2).map(|&n| Foreground::Basic(n.min(255) as u8)), 2),
+                        Some(2) => (
+                            codes
+                                .get(i + 2..i + 5)
+                                .map(|c| Foreground::Rgb((c[0].min(255) as u8, c[1].min(255) as u8, c[2].min(255) as u8))),
+                            4,
+                        ),
+                        _ => (None, 1),
+                    };
+                    if extended == 38 && let Some(value) = value {
+                        self.foreground = value;
+                    }
+                    i += used;
+                }
+                _ => {}
+            }
+            i += 1;
+        }
+    }
+}
+
+/// Parses plain or ANSI-colored text into a grid. Characters take the color
+/// of the last SGR foreground code, brightened by bold (`ESC [ 1 m`), or
+/// `default` when none is active. Background colors are not recorded.
+/// Cursor-forward (`ESC [ n C`) and tabs (every 8 columns) advance with
+/// spaces, other escape sequences are ignored, and short lines are padded
+/// with spaces to the widest line. With `columns`, lines wrap like a
+/// terminal of that width: a character past the edge starts a new line, so a
+/// full line followed by a newline does not leave a blank one.
+pub fn parse(text: &str, default: (u8, u8, u8), columns: Option<usize>) -> AsciiGrid {
+    let mut grid: AsciiGrid = Vec::new();
+    let mut row: Vec<AsciiPixel> = Vec::new();
+    let mut pen = Pen { foreground: Foreground::Default, bold: false };
+    let mut color = default;
+    let mut chars = text.chars().peekable();
+    let limit = columns.filter(|&columns| columns > 0).unwrap_or(usize::MAX);
+    let advance = |row: &mut Vec<AsciiPixel>, count: usize, color| {
+        let count = count.min(limit.saturating_sub(row.len()));
+        row.extend(std::iter::repeat_n(AsciiPixel { character: ' ', color }, count));
+    };
+
+    while let Some(ch) = chars.next() {
+        match ch {
+            ESC => match chars.next() {
+                Some('[') => {
+                    let mut params = String::new();
+                    for c in chars.by_ref() {
+                        if ('\x40'..='\x7e').contains(&c) {
+                            match c {
+                                'm' => {
+                                    pen.apply_sgr(&params);
+                                    color = pen.color(default);
+                                }
+                                'C' => advance(&mut row, params.parse().unwrap_or(1).clamp(1, u16::MAX as usize), color),
+                                _ => {}
+                            }
+                            break;
+                        }
+                        params.push(c);
+                    }
+                }
+                // OSC and other string sequences end with BEL or ESC \.
+                Some(']' | 'P' | '_' | '^') => {
+                    while let Some(c) = chars.next() {
+                        if c == '\x07' || (c == ESC && chars.next_if_eq(&'\\').is_some()) {
+                            break;
+                        }
+                    }
+                }
+                _ => {}
+            },
+            '\n' => grid.push(std::mem::take(&mut row)),
+            '\t' => {
+                let spaces = TAB_WIDTH - row.len() % TAB_WIDTH;
+                advance(&mut row, spaces, color);
+            }
+            c if c.is_control() => {}
+            character => {
+                if row.len() >= limit {
+                    grid.push(std::mem::take(&mut row));
+                }
+                row.push(AsciiPixel { character, color });
+            }
+        }
+    }
+    if !row.is_empty() {
+        grid.push(row);
+    }
+
+    let width = grid.iter().map(Vec::len).max().unwrap_or(0);
+    for row in &mut grid {
+        row.resize(width, AsciiPixel { character: ' ', color: default });
+    }
+    grid
+}
+
+/// Writes rows as terminal text with as few escapes as possible. A color
+/// run continues while every channel stays within `tolerance` of the color
+/// that started it, spaces never switch colors (they show no foreground),
+/// and the color carries across lines with a single reset at the end.
+/// `Mono` writes plain text and `Ansi256` uses 256-color escapes.
+pub struct AnsiWriter<W: Write> {
+    out: W,
+    mode: ColorMode,
+    tolerance: u8,
+    current: Option<(u8, u8, u8)>,
+    line: String,
+}
+
+fn within(a: (u8, u8, u8), b: (u8, u8, u8), tolerance: u8) -> bool {
+    a.0.abs_diff(b.0) <= tolerance && a.1.abs_diff(b.1) <= tolerance && a.2.abs_diff(b.2) <= tolerance
+}
+
+impl<W: Write> AnsiWriter<W> {
+    pub fn new(out: W, mode: ColorMode, tolerance: u8) -> AnsiWriter<W> {
+        AnsiWriter { out, mode, tolerance, current: None, line: String::new() }
+    }
+
+    pub fn write_row(&mut self, row: &[AsciiPixel]) -> io::Result<()> {
+        self.line.clear();
+        for pixel in row {
+            let keep = pixel.character == ' ' || self.current.is_some_and(|current| within(current, pixel.color, self.tolerance));
+            if self.mode != ColorMode::Mono && !keep {
+                let (r, g, b) = pixel.color;
+                let _ = match self.mode {
+                    ColorMode::Ansi256 => write!(self.line, "\x1b[38;5;{}m", rgb_to_ansi256(pixel.color)),
+                    _ => write!(self.line, "\x1b[38;2;{};{};{}m", r, g, b),
+                };
+                self.current = Some(pixel.color);
+            }
+            self.line.push(pixel.character);
+        }
+        self.line.push('\n');
+        self.out.write_all(self.line.as_bytes())
+    }
+
+    /// Resets the color if one is active and flushes.
+    pub fn finish(mut self) -> io::Result<W> {
+        if self.current.is_some() {
+            self.out.write_all(RESET.as_bytes())?;
+        }
+        self.out.flush()?;
+        Ok(self.out)
+    }
+}
+
+/// Streams `rows` to `out`, e.g. straight from `convert_rows`.
+pub fn write_rows<R: AsRef<[AsciiPixel]>>(out: impl Write, rows: impl IntoIterator<Item = R>, mode: ColorMode, tolerance: u8) -> io::Result<()> {
+    let mut writer = AnsiWriter::new(out, mode, tolerance);
+    for row in rows {
+        writer.write_row(row.as_ref())?;
+    }
+    writer.finish().map(drop)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WHITE: (u8, u8, u8) = (255, 255, 255);
+
+    fn text(grid: &AsciiGrid) -> Vec<String> {
+        grid.iter().map(|row| row.iter().map(|pixel| pixel.character).collect()).collect()
+    }
+
+    #[test]
+    fn cursor_forward_advances_with_spaces() {
+        let grid = parse("a\x1b[3Cb\x1b[Cc", WHITE, None);
+        assert_eq!(text(&grid), ["a   b c"]);
+    }
+
+    #[test]
+    fn cursor_forward_stops_at_the_edge() {
+        let grid = parse("ab\x1b[10Cc", WHITE, Some(4));
+        assert_eq!(text(&grid), ["ab  ", "c   "]);
+    }
+
+    #[test]
+    fn long_lines_wrap_without_blank_lines() {
+        let grid = parse("abcdef\nghij\r\nk", WHITE, Some(4));
+        assert_eq!(text(&grid), ["abcd", "ef  ", "ghij", "k   "]);
+    }
+
+    #[test]
+    fn unwrapped_lines_pad_to_the_widest() {
+        let grid = parse("abcdef\ng", WHITE, None);
+        assert_eq!(text(&grid), ["abcdef", "g     "]);
+    }
+
+    #[test]
+    fn sauce_width_and_fallbacks() {
+        let mut bytes = b"art\x1a".to_vec();
+        let mut record = vec![0u8; SAUCE_SIZE];
+        record[..7].copy_from_slice(b"SAUCE00");
+        record[94] = 1;
+        record[96..98].copy_from_slice(&160u16.to_le_bytes());
+        bytes.extend(record);
+        assert_eq!(sauce_width(&bytes), Some(160));
+        assert_eq!(art_columns(&bytes), Some(160));
+
+        assert_eq!(art_columns(b"plain text"), None);
+        assert_eq!(art_columns(b"\xdb\xdb"), Some(DOS_COLUMNS));
+    }
+
+    fn colors(grid: &AsciiGrid) -> Vec<(u8, u8, u8)> {
+        grid[0].iter().map(|pixel| pixel.color).collect()
+    }
+
+    #[test]
+    fn bold_brightens_basic_colors() {
+        let red = ansi256_to_rgb(1);
+        let bright_red = ansi256_to_rgb(9);
+        let grid = parse("\x1b[1;31ma\x1b[31;1mb\x1b[22mc\x1b[0;31md\x1b[1me", WHITE, None);
+        assert_eq!(colors(&grid), [bright_red, bright_red, red, red, bright_red]);
+    }
+
+    #[test]
+    fn resets_and_extended_colors() {
+        let grid = parse("\x1b[1;32ma\x1b[0mb\x1b[38;2;1;2;3mc\x1b[39md\x1b[38;5;196me", WHITE, None);
+        assert_eq!(colors(&grid), [ansi256_to_rgb(10), WHITE, (1, 2, 3), WHITE, (255, 0, 0)]);
+    }
+
+    #[test]
+    fn background_codes_leave_the_foreground_alone() {
+        let grid = parse("\x1b[33;44ma\x1b[48;2;9;9;9mb\x1b[48;5;21;1mc", WHITE, None);
+        assert_eq!(colors(&grid), [ansi256_to_rgb(3), ansi256_to_rgb(3), ansi256_to_rgb(11)]);
+    }
+
+    #[test]
+    fn cp437_art_decodes_to_blocks() {
+        assert_eq!(decode(b"\xdb\xdc\xdf\xb0\xb1\xb2\x1aSAUCE"), "█▄▀░▒▓");
+        assert_eq!(decode("café".as_bytes()), "café");
+    }
+
+    fn written(rows: &[Vec<AsciiPixel>], mode: ColorMode, tolerance: u8) -> String {
+        let mut out = Vec::new();
+        write_rows(&mut out, rows, mode, tolerance).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    fn row(cells: &[(char, (u8, u8, u8))]) -> Vec<AsciiPixel> {
+        cells.iter().map(|&(character, color)| AsciiPixel { character, color }).collect()
+    }
+
+    #[test]
+    fn colors_within_the_tolerance_share_one_escape() {
+        let rows = [row(&[('a', (100, 100, 100)), ('b', (104, 96, 108)), ('c', (109, 100, 100))])];
+        assert_eq!(written(&rows, ColorMode::Truecolor, 8), "\x1b[38;2;100;100;100mab\x1b[38;2;109;100;100mc\n\x1b[0m");
+        assert_eq!(written(&rows, ColorMode::Truecolor, 0).matches("\x1b[38;2").count(), 3);
+    }
+
+    #[test]
+    fn spaces_do_not_switch_colors() {
+        let rows = [row(&[('a', (255, 0, 0)), (' ', (0, 0, 255)), (' ', (0, 255, 0)), ('b', (255, 0, 0))])];
+        assert_eq!(written(&rows, ColorMode::Truecolor, 0), "\x1b[38;2;255;0;0ma  b\n\x1b[0m");
+    }
+
+    #[test]
+    fn colors_carry_across_rows_with_one_reset() {
+        let rows = [row(&[('a', (1, 2, 3))]), row(&[('b', (1, 2, 3))]), row(&[('c', (200, 2, 3))])];
+        let output = written(&rows, ColorMode::Truecolor, 0);
+        assert_eq!(output, "\x1b[38;2;1;2;3ma\nb\n\x1b[38;2;200;2;3mc\n\x1b[0m");
+        assert_eq!(output.matches(RESET).count(), 1);
+        assert!(output.ends_with(RESET));
+    }
+
+    #[test]
+    fn blank_output_needs_no_reset() {
+        assert_eq!(written(&[row(&[(' ', (1, 2, 3))])], ColorMode::Truecolor, 0), " \n");
+    }
+
+    #[test]
+    fn ansi256_and_mono_modes() {
+        let rows = [row(&[('a', (255, 0, 0)), ('b', (0, 0, 255))])];
+        assert_eq!(written(&rows, ColorMode::Ansi256, 0), "\x1b[38;5;196ma\x1b[38;5;21mb\n\x1b[0m");
+        assert_eq!(written(&rows, ColorMode::Mono, 0), "ab\n");
+    }
+}
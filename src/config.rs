@@ -0,0 +1,171 @@
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Conversion settings as written in the config file, either at the top
+/// level (defaults) or inside a `[presets.<name>]` table. Values stay as
+/// strings until they are resolved against the command line so that errors
+/// can name the offending setting.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Settings {
+    pub density: Option<String>,
+    pub width: Option<u32>,
+    pub ramp: Option<String>,
+    pub filter: Option<String>,
+    pub color: Option<String>,
+    pub palette: Option<Vec<String>>,
+    pub luminance: Option<String>,
+    pub brightness: Option<f32>,
+    pub contrast: Option<f32>,
+    pub gamma: Option<f32>,
+    pub auto_levels: Option<bool>,
+    pub equalize: Option<bool>,
+    pub invert: Option<bool>,
+}
+
+impl Settings {
+    /// Layers `other` on top of `self`; values set in `other` win.
+    pub fn overlay(&self, other: &Settings) -> Settings {
+        Settings {
+            density: other.density.clone().or_else(|| self.density.clone()),
+            width: other.width.or(self.width),
+            ramp: other.ramp.clone().or_else(|| self.ramp.clone()),
+            filter: other.filter.clone().or_else(|| self.filter.clone()),
+            color: other.color.clone().or_else(|| self.color.clone()),
+            palette: other.palette.clone().or_else(|| self.palette.clone()),
+            luminance: other.luminance.clone().or_else(|| self.luminance.clone()),
+            brightness: other.brightness.or(self.brightness),
+            contrast: other.contrast.or(self.contrast),
+            gamma: other.gamma.or(self.gamma),
+            auto_levels: other.auto_levels.or(self.auto_levels),
+            equalize: other.equalize.or(self.equalize),
+            invert: other.invert.or(self.invert),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Config {
+    pub output_dir: Option<PathBuf>,
+    pub presets: HashMap<String, Settings>,
+    pub defaults: Settings,
+}
+
+/// `$XDG_CONFIG_HOME/bitify/config.toml`, falling back to
+/// `~/.config/bitify/config.toml`.
+pub fn default_config_path() -> Option<PathBuf> {
+    if let Some(dir) = env::var_os("XDG_CONFIG_HOME").filter(|dir| !dir.is_empty()) {
+        return Some(PathBuf::from(dir).join("bitify").join("config.toml"));
+    }
+    dirs::home_dir().map(|home| home.join(".config").join("bitify").join("config.toml"))
+}
+
+fn expand_home(path: &Path) -> PathBuf {
+    match path.strip_prefix("~") {
+        Ok(rest) => dirs::home_dir().map_or_else(|| path.to_path_buf(), |home| home.join(rest)),
+        Err(_) => path.to_path_buf(),
+    }
+}
+
+impl Config {
+    /// Loads `explicit` if given (it must exist), otherwise the default config
+    /// path if present. A missing default config is not an error.
+    pub fn load(explicit: Option<&Path>) -> Result<Config, String> {
+        let (path, required) = match explicit {
+            Some(path) => (path.to_path_buf(), true),
+            None => match default_config_path() {
+                Some(path) => (path, false),
+                None => return Ok(Config::default()),
+            },
+        };
+
+        let text = match fs::read_to_string(&path) {
+            Ok(text) => text,
+            Err(e) if !required && e.kind() == io::ErrorKind::NotFound => return Ok(Config::default()),
+            Err(e) => return Err(format!("Could not read config {}: {}", path.display(), e)),
+        };
+        Config::parse(&text).map_err(|e| format!("Invalid config {}: {}", path.display(), e))
+    }
+
+    /// The top-level defaults are deserialized as a `Settings` of their own
+    /// rather than flattened, which would let misspelled keys through.
+    fn parse(text: &str) -> Result<Config, toml::de::Error> {
+        let mut table: toml::Table = toml::from_str(text)?;
+        let output_dir = table.remove("output_dir").map(toml::Value::try_into).transpose()?;
+        let presets = table.remove("presets").map(toml::Value::try_into).transpose()?.unwrap_or_default();
+        let defaults = toml::Value::Table(table).try_into()?;
+        Ok(Config { output_dir, presets, defaults })
+    }
+
+    pub fn preset(&self, name: &str) -> Option<&Settings> {
+        self.presets.get(name)
+    }
+
+    /// Where PNGs are saved: `output_dir` from the config, or `~/Bitify`.
+    pub fn output_dir(&self) -> Result<PathBuf, String> {
+        match &self.output_dir {
+            Some(dir) => Ok(expand_home(dir)),
+            None => dirs::home_dir()
+                .map(|home| home.join("Bitify"))
+                .ok_or_else(|| "Could not find home directory".to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE: &str = r##"
+output_dir = "/tmp/art"
+density = "high"
+width = 100
+gamma = 1.2
+
+[presets.print]
+invert = true
+width = 60
+palette = ["#000", "#fff"]
+"##;
+
+    #[test]
+    fn parses_defaults_and_presets() {
+        let config = Config::parse(SAMPLE).unwrap();
+        assert_eq!(config.output_dir, Some(PathBuf::from("/tmp/art")));
+        assert_eq!(config.defaults.density.as_deref(), Some("high"));
+        assert_eq!(config.defaults.gamma, Some(1.2));
+        let print = config.preset("print").unwrap();
+        assert_eq!(print.invert, Some(true));
+        assert_eq!(print.palette.as_ref().map(Vec::len), Some(2));
+        assert!(config.preset("screen").is_none());
+    }
+
+    #[test]
+    fn rejects_unknown_keys() {
+        let top = Config::parse("densty = \"high\"\n").unwrap_err();
+        assert!(top.to_string().contains("densty"), "{}", top);
+        assert!(Config::parse("[presets.print]\ninvret = true\n").is_err());
+        assert!(Config::parse("width = \"wide\"\n").is_err());
+    }
+
+    #[test]
+    fn overlay_prefers_the_upper_layer() {
+        let config = Config::parse(SAMPLE).unwrap();
+        let merged = config.defaults.overlay(config.preset("print").unwrap());
+        assert_eq!(merged.width, Some(60));
+        assert_eq!(merged.density.as_deref(), Some("high"));
+        assert_eq!(merged.gamma, Some(1.2));
+        assert_eq!(merged.invert, Some(true));
+    }
+
+    #[test]
+    fn empty_config_has_no_settings() {
+        let config = Config::parse("").unwrap();
+        assert!(config.output_dir.is_none() && config.presets.is_empty());
+        assert!(config.defaults.density.is_none());
+    }
+}
@@ -0,0 +1,189 @@
+use image::{DynamicImage, GenericImageView};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Extent {
+    Pixels(u32),
+    Percent(f32),
+}
+
+impl Extent {
+    fn resolve(&self, total: u32) -> u32 {
+        match *self {
+            Extent::Pixels(px) => px,
+            Extent::Percent(pct) => (total as f32 * pct / 100.0).round() as u32,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Crop {
+    pub x: Extent,
+    pub y: Extent,
+    pub width: Extent,
+    pub height: Extent,
+}
+
+impl std::fmt::Display for Extent {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Extent::Pixels(px) => write!(f, "{}", px),
+            Extent::Percent(pct) => write!(f, "{}%", pct),
+        }
+    }
+}
+
+impl std::fmt::Display for Crop {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{},{},{},{}", self.x, self.y, self.width, self.height)
+    }
+}
+
+fn parse_extent(s: &str) -> Result<Extent, String> {
+    let s = s.trim();
+    if let Some(pct) = s.strip_suffix('%') {
+        let pct: f32 = pct.parse().map_err(|_| format!("Invalid percentage '{}'", s))?;
+        if !(0.0..=100.0).contains(&pct) {
+            return Err(format!("Percentage '{}' must be between 0% and 100%", s));
+        }
+        Ok(Extent::Percent(pct))
+    } else {
+        let px: u32 = s.parse().map_err(|_| format!("Invalid pixel value '{}'", s))?;
+        Ok(Extent::Pixels(px))
+    }
+}
+
+pub fn parse_crop(s: &str) -> Result<Crop, String> {
+    let parts: Vec<&str> = s.split(',').collect();
+    if parts.len() != 4 {
+        return Err(format!("Invalid crop '{}'. Use: x,y,w,h (pixels or percentages, e.g. 10%,0,50%,100%)", s));
+    }
+    Ok(Crop {
+        x: parse_extent(parts[0])?,
+        y: parse_extent(parts[1])?,
+        width: parse_extent(parts[2])?,
+        height: parse_extent(parts[3])?,
+    })
+}
+
+impl Crop {
+    /// Cuts the region out of `img`, clamping it to the image bounds.
+    pub fn apply(&self, img: &DynamicImage) -> Result<DynamicImage, String> {
+        let (img_width, img_height) = img.dimensions();
+        let x = self.x.resolve(img_width);
+        let y = self.y.resolve(img_height);
+        if x >= img_width || y >= img_height {
+            return Err(format!("Crop origin {},{} lies outside the {}x{} image", x, y, img_width, img_height));
+        }
+
+        let width = self.width.resolve(img_width).min(img_width - x);
+        let height = self.height.resolve(img_height).min(img_height - y);
+        if width == 0 || height == 0 {
+            return Err("Crop region is empty".to_string());
+        }
+
+        Ok(img.crop_imm(x, y, width, height))
+    }
+}
+
+/// Removes borders whose pixels all stay within `tolerance` (per channel) of
+/// the top-left corner color. Images that are entirely border are returned
+/// unchanged.
+pub fn trim(img: &DynamicImage, tolerance: u8) -> DynamicImage {
+    let rgba = img.to_rgba8();
+    let (width, height) = rgba.dimensions();
+    if width == 0 || height == 0 {
+        return img.clone();
+    }
+
+    let border = rgba.get_pixel(0, 0).0;
+    let is_content = |x: u32, y: u32| {
+        let pixel = rgba.get_pixel(x, y).0;
+        pixel
+            .iter()
+            .zip(border.iter())
+            .any(|(&a, &b)| a.abs_diff(b) > tolerance)
+    };
+
+    let mut min_x = width;
+    let mut min_y = height;
+    let mut max_x = 0;
+    let mut max_y = 0;
+    for y in 0..height {
+        for x in 0..width {
+            if is_content(x, y) {
+                min_x = min_x.min(x);
+                min_y = min_y.min(y);
+                max_x = max_x.max(x);
+                max_y = max_y.max(y);
+            }
+        }
+    }
+
+    if min_x > max_x || min_y > max_y {
+        return img.clone();
+    }
+    img.crop_imm(min_x, min_y, max_x - min_x + 1, max_y - min_y + 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{Rgb, RgbImage};
+
+    fn blank(width: u32, height: u32) -> DynamicImage {
+        DynamicImage::ImageRgb8(RgbImage::new(width, height))
+    }
+
+    #[test]
+    fn parses_pixels_and_percentages() {
+        let crop = parse_crop("10, 20,50%,100%").unwrap();
+        assert_eq!(crop.x, Extent::Pixels(10));
+        assert_eq!(crop.y, Extent::Pixels(20));
+        assert_eq!(crop.width, Extent::Percent(50.0));
+        assert_eq!(crop.height, Extent::Percent(100.0));
+        assert_eq!(crop.to_string(), "10,20,50%,100%");
+        assert_eq!(parse_crop(&crop.to_string()), Ok(crop));
+
+        for bad in ["1,2,3", "1,2,3,4,5", "-1,0,1,1", "0,0,101%,1", "a,0,1,1"] {
+            assert!(parse_crop(bad).is_err(), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn percentages_resolve_against_the_image() {
+        let crop = parse_crop("25%,50%,50%,50%").unwrap();
+        let cropped = crop.apply(&blank(200, 100)).unwrap();
+        assert_eq!(cropped.dimensions(), (100, 50));
+    }
+
+    #[test]
+    fn regions_are_clamped_to_the_image() {
+        let crop = parse_crop("30,10,100,100").unwrap();
+        assert_eq!(crop.apply(&blank(40, 20)).unwrap().dimensions(), (10, 10));
+    }
+
+    #[test]
+    fn origins_outside_the_image_are_errors() {
+        assert!(parse_crop("40,0,10,10").unwrap().apply(&blank(40, 20)).is_err());
+        assert!(parse_crop("0,100%,10,10").unwrap().apply(&blank(40, 20)).is_err());
+        assert!(parse_crop("0,0,0,10").unwrap().apply(&blank(40, 20)).is_err());
+    }
+
+    #[test]
+    fn trim_removes_uniform_borders() {
+        let mut img = RgbImage::from_pixel(10, 8, Rgb([255, 255, 255]));
+        img.put_pixel(3, 2, Rgb([0, 0, 0]));
+        img.put_pixel(6, 4, Rgb([0, 0, 0]));
+        // Within tolerance of the border, so still trimmed away.
+        img.put_pixel(8, 7, Rgb([250, 250, 250]));
+        let trimmed = trim(&DynamicImage::ImageRgb8(img), 16);
+        assert_eq!(trimmed.dimensions(), (4, 3));
+        assert_eq!(trimmed.get_pixel(0, 0).0, [0, 0, 0, 255]);
+    }
+
+    #[test]
+    fn trim_keeps_images_that_are_all_border() {
+        let img = DynamicImage::ImageRgb8(RgbImage::from_pixel(5, 4, Rgb([9, 9, 9])));
+        assert_eq!(trim(&img, 0).dimensions(), (5, 4));
+    }
+}
@@ -0,0 +1,193 @@
+//! C ABI for embedding the converter; declarations are in `include/bitify.h`.
+//! Build the shared library with `cargo build --release --lib --features ffi`.
+
+use crate::ansi::{self, DEFAULT_COLOR_TOLERANCE};
+use crate::palette::ColorMode;
+use crate::{AsciiGrid, ConvertOptions, DensityPreset, convert_bytes, html};
+use std::ffi::{CStr, CString, c_char};
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+
+pub const BITIFY_FORMAT_PNG: u32 = 0;
+pub const BITIFY_FORMAT_TEXT: u32 = 1;
+pub const BITIFY_FORMAT_ANSI: u32 = 2;
+pub const BITIFY_FORMAT_HTML: u32 = 3;
+
+/// Mirrors `bitify_options` in the header. Densities and color modes are
+/// indices in the order the command lists them.
+#[repr(C)]
+pub struct BitifyOptions {
+    /// 0 low, 1 medium, 2 high, 3 ultra, 4 extreme.
+    pub density: u32,
+    /// Characters per row; 0 uses the density's default.
+    pub width: u32,
+    /// 0 truecolor, 1 256-color, 2 mono.
+    pub color: u32,
+    pub invert: bool,
+    pub format: u32,
+    /// Custom ramp, darkest first, as UTF-8; null for the density's ramp.
+    pub charset: *const c_char,
+}
+
+/// Result of `bitify_convert`: either `data`/`len` or a NUL-terminated
+/// `error`, never both. Release with `bitify_buffer_free`.
+#[repr(C)]
+pub struct BitifyBuffer {
+    pub data: *mut u8,
+    pub len: usize,
+    pub error: *mut c_char,
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn bitify_default_options() -> BitifyOptions {
+    BitifyOptions { density: 1, width: 0, color: 0, invert: false, format: BITIFY_FORMAT_PNG, charset: ptr::null() }
+}
+
+/// # Safety
+///
+/// `opts.charset` must be null or point to a NUL-terminated string.
+unsafe fn to_options(opts: &BitifyOptions) -> Result<ConvertOptions, String> {
+    let density = DensityPreset::ALL
+        .get(opts.density as usize)
+        .cloned()
+        .ok_or_else(|| format!("Invalid density {}", opts.density))?;
+    let mut options = ConvertOptions::new(density);
+    if opts.width > 0 {
+        options.width = opts.width;
+    }
+    options.color = match opts.color {
+        0 => ColorMode::Truecolor,
+        1 => ColorMode::Ansi256,
+        2 => ColorMode::Mono,
+        color => return Err(format!("Invalid color mode {}", color)),
+    };
+    options.invert = opts.invert;
+    if !opts.charset.is_null() {
+        let charset = unsafe { CStr::from_ptr(opts.charset) }.to_str().map_err(|_| "Charset is not UTF-8".to_string())?;
+        options.charset = Some(crate::parse_charset(charset)?);
+    }
+    Ok(options)
+}
+
+fn encode(ascii_data: &AsciiGrid, format: u32, options: &ConvertOptions) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    let mode = match format {
+        #[cfg(feature = "png-render")]
+        BITIFY_FORMAT_PNG => return crate::encode_ascii_png(ascii_data, "-", options),
+        #[cfg(not(feature = "png-render"))]
+        BITIFY_FORMAT_PNG => return Err("PNG output needs the png-render feature".into()),
+        BITIFY_FORMAT_HTML => return Ok(html::render_page(ascii_data, options).into_bytes()),
+        BITIFY_FORMAT_TEXT => ColorMode::Mono,
+        BITIFY_FORMAT_ANSI => options.color,
+        format => return Err(format!("Invalid format {}", format).into()),
+    };
+    let mut bytes = Vec::new();
+    ansi::write_rows(&mut bytes, ascii_data, mode, DEFAULT_COLOR_TOLERANCE)?;
+    Ok(bytes)
+}
+
+fn error_buffer(message: &str) -> BitifyBuffer {
+    let message = CString::new(message.replace('\0', " ")).unwrap_or_default();
+    BitifyBuffer { data: ptr::null_mut(), len: 0, error: message.into_raw() }
+}
+
+/// Converts encoded image bytes (any format the command reads) to the
+/// output selected by `opts.format`. A null `opts` uses the defaults.
+///
+/// # Safety
+///
+/// `bytes` must point to `len` readable bytes, and `opts` must be null or
+/// point to a valid `BitifyOptions`.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn bitify_convert(bytes: *const u8, len: usize, opts: *const BitifyOptions) -> BitifyBuffer {
+    if bytes.is_null() {
+        return error_buffer("No image bytes");
+    }
+    let bytes = unsafe { std::slice::from_raw_parts(bytes, len) };
+    let defaults = bitify_default_options();
+    let opts = unsafe { opts.as_ref() }.unwrap_or(&defaults);
+
+    // Panics must not unwind into C.
+    let result = panic::catch_unwind(AssertUnwindSafe(|| -> Result<Vec<u8>, String> {
+        let options = unsafe { to_options(opts) }?;
+        let ascii_data = convert_bytes(bytes, &options).map_err(|e| e.to_string())?;
+        encode(&ascii_data, opts.format, &options).map_err(|e| e.to_string())
+    }));
+    match result {
+        Ok(Ok(data)) => {
+            let len = data.len();
+            BitifyBuffer { data: Box::into_raw(data.into_boxed_slice()).cast(), len, error: ptr::null_mut() }
+        }
+        Ok(Err(message)) => error_buffer(&message),
+        Err(_) => error_buffer("Conversion panicked"),
+    }
+}
+
+/// # Safety
+///
+/// `buffer` must come from `bitify_convert` and not have been freed yet.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn bitify_buffer_free(buffer: BitifyBuffer) {
+    if !buffer.data.is_null() {
+        drop(unsafe { Box::from_raw(ptr::slice_from_raw_parts_mut(buffer.data, buffer.len)) });
+    }
+    if !buffer.error.is_null() {
+        drop(unsafe { CString::from_raw(buffer.error) });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PPM: &[u8] = b"P6\n2 1\n255\n\xff\xff\xff\x00\x00\x00";
+
+    /// Runs `bitify_convert` and frees the buffer, returning its contents.
+    fn convert(bytes: &[u8], opts: Option<&BitifyOptions>) -> Result<Vec<u8>, String> {
+        let opts = opts.map_or(ptr::null(), |opts| opts as *const _);
+        let buffer = unsafe { bitify_convert(bytes.as_ptr(), bytes.len(), opts) };
+        let result = if buffer.error.is_null() {
+            assert!(!buffer.data.is_null());
+            Ok(unsafe { std::slice::from_raw_parts(buffer.data, buffer.len) }.to_vec())
+        } else {
+            assert!(buffer.data.is_null());
+            Err(unsafe { CStr::from_ptr(buffer.error) }.to_string_lossy().into_owned())
+        };
+        unsafe { bitify_buffer_free(buffer) };
+        result
+    }
+
+    #[test]
+    fn converts_to_each_format() {
+        let charset = CString::new(" #").unwrap();
+        let mut opts = BitifyOptions { width: 2, format: BITIFY_FORMAT_TEXT, charset: charset.as_ptr(), ..bitify_default_options() };
+        assert_eq!(convert(PPM, Some(&opts)).unwrap(), b"# \n");
+        opts.format = BITIFY_FORMAT_ANSI;
+        assert!(convert(PPM, Some(&opts)).unwrap().starts_with(b"\x1b[38;2;"));
+        opts.format = BITIFY_FORMAT_HTML;
+        assert!(convert(PPM, Some(&opts)).unwrap().starts_with(b"<!DOCTYPE html>"));
+        #[cfg(feature = "png-render")]
+        assert!(convert(PPM, None).unwrap().starts_with(b"\x89PNG"));
+    }
+
+    #[test]
+    fn zero_width_uses_the_density_default() {
+        let opts = unsafe { to_options(&bitify_default_options()) }.unwrap();
+        assert_eq!(opts, ConvertOptions::new(DensityPreset::Medium));
+    }
+
+    #[test]
+    fn bad_input_is_reported_as_an_error() {
+        let invalid = [
+            BitifyOptions { density: 5, ..bitify_default_options() },
+            BitifyOptions { color: 3, ..bitify_default_options() },
+            BitifyOptions { format: 9, ..bitify_default_options() },
+        ];
+        for opts in &invalid {
+            assert!(convert(PPM, Some(opts)).unwrap_err().starts_with("Invalid"));
+        }
+        assert!(convert(b"not an image", None).is_err());
+        let buffer = unsafe { bitify_convert(ptr::null(), 0, ptr::null()) };
+        assert_eq!(unsafe { CStr::from_ptr(buffer.error) }.to_str(), Ok("No image bytes"));
+        unsafe { bitify_buffer_free(buffer) };
+    }
+}
@@ -0,0 +1,180 @@
+use crate::render::{builtin_pattern, get_char_pattern};
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+/// Largest cell side, in pixels, for fonts and resampled glyphs.
+pub const MAX_CELL_SIDE: u32 = 256;
+
+/// Glyphs for drawing characters into PNG cells: the built-in 8x12 patterns
+/// or a bitmap font loaded from a BDF file.
+pub enum Font {
+    Builtin,
+    Bdf(BdfFont),
+}
+
+pub struct BdfFont {
+    width: u32,
+    height: u32,
+    /// Row-major `width * height` bitmaps, already placed in the cell.
+    glyphs: HashMap<char, Vec<bool>>,
+}
+
+impl Font {
+    /// Native cell size of the font in pixels.
+    pub fn size(&self) -> (u32, u32) {
+        match self {
+            Font::Builtin => (8, 12),
+            Font::Bdf(font) => (font.width, font.height),
+        }
+    }
+
+    /// Whether the font has its own glyph for `ch`; see `glyph` for what
+    /// is drawn otherwise. Whitespace needs none.
+    pub fn has_glyph(&self, ch: char) -> bool {
+        ch.is_whitespace()
+            || match self {
+                Font::Builtin => builtin_pattern(ch).is_some(),
+                Font::Bdf(font) => font.glyphs.contains_key(&ch),
+            }
+    }
+
+    /// Bitmap for `ch` at the native size, row-major. Characters the font
+    /// lacks draw as `?`, or blank if that is missing too.
+    pub fn glyph(&self, ch: char) -> Option<&[bool]> {
+        match self {
+            Font::Builtin => Some(get_char_pattern(ch).as_flattened()),
+            Font::Bdf(font) => font
+                .glyphs
+                .get(&ch)
+                .or_else(|| font.glyphs.get(&'?'))
+                .map(Vec::as_slice),
+        }
+    }
+}
+
+fn parse_numbers(line: &str, keyword: &str, count: usize) -> Result<Vec<i32>, String> {
+    let numbers = line
+        .split_whitespace()
+        .skip(1)
+        .map(|n| n.parse::<i32>())
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|_| format!("Invalid {} line '{}'", keyword, line))?;
+    if numbers.len() < count {
+        return Err(format!("Invalid {} line '{}'", keyword, line));
+    }
+    Ok(numbers)
+}
+
+/// Loads a BDF bitmap font. Glyph encodings are taken as Unicode code points
+/// (true for ISO 10646 and ASCII-range Latin-1 fonts); the cell size is the
+/// font bounding box. Glyphs may be up to 64 pixels wide.
+pub fn load_bdf(path: &Path) -> Result<Font, String> {
+    let bytes = fs::read(path).map_err(|e| format!("Could not read font {}: {}", path.display(), e))?;
+    let text = String::from_utf8_lossy(&bytes);
+    let invalid = |message: String| format!("Invalid BDF font {}: {}", path.display(), message);
+
+    let mut bounds: Option<(u32, u32, i32, i32)> = None;
+    let mut glyphs = HashMap::new();
+    let mut lines = text.lines().map(str::trim);
+
+    while let Some(line) = lines.next() {
+        if line.starts_with("FONTBOUNDINGBOX") {
+            let n = parse_numbers(line, "FONTBOUNDINGBOX", 4).map_err(invalid)?;
+            if n[0] <= 0 || n[1] <= 0 {
+                return Err(invalid("empty bounding box".to_string()));
+            }
+            if n[0] as u32 > MAX_CELL_SIDE || n[1] as u32 > MAX_CELL_SIDE {
+                return Err(invalid(format!("bounding box {}x{} is larger than {} pixels a side", n[0], n[1], MAX_CELL_SIDE)));
+            }
+            bounds = Some((n[0] as u32, n[1] as u32, n[2], n[3]));
+        } else if line.starts_with("STARTCHAR") {
+            let (width, height, font_x, font_y) = bounds.ok_or_else(|| invalid("glyph before FONTBOUNDINGBOX".to_string()))?;
+            let mut encoding = -1;
+            let mut bbx = (0, 0, 0, 0);
+            let mut bitmap = vec![false; (width * height) as usize];
+
+            for line in lines.by_ref() {
+                if line.starts_with("ENCODING") {
+                    encoding = parse_numbers(line, "ENCODING", 1).map_err(invalid)?[0];
+                } else if line.starts_with("BBX") {
+                    let n = parse_numbers(line, "BBX", 4).map_err(invalid)?;
+                    bbx = (n[0], n[1], n[2], n[3]);
+                } else if line == "BITMAP" {
+                    break;
+                }
+            }
+
+            // Rows are listed top-down; the baseline sits `font_y` above the
+            // bottom of the cell and the glyph box `bbx.3` above the baseline.
+            let top = height as i32 + font_y - (bbx.3 + bbx.1);
+            let left = bbx.2 - font_x;
+            for (row, line) in lines.by_ref().take_while(|line| *line != "ENDCHAR").enumerate() {
+                let bits = u64::from_str_radix(line, 16).map_err(|_| invalid(format!("bad bitmap row '{}'", line)))?;
+                let row_bits = line.len() as i32 * 4;
+                for col in 0..bbx.0.min(row_bits) {
+                    let (x, y) = (left + col, top + row as i32);
+                    if bits >> (row_bits - 1 - col) & 1 == 1 && (0..width as i32).contains(&x) && (0..height as i32).contains(&y) {
+                        bitmap[(y as u32 * width + x as u32) as usize] = true;
+                    }
+                }
+            }
+
+            if let Some(ch) = u32::try_from(encoding).ok().and_then(char::from_u32) {
+                glyphs.insert(ch, bitmap);
+            }
+        }
+    }
+
+    let (width, height, _, _) = bounds.ok_or_else(|| invalid("missing FONTBOUNDINGBOX".to_string()))?;
+    if glyphs.is_empty() {
+        return Err(invalid("no glyphs".to_string()));
+    }
+    Ok(Font::Bdf(BdfFont { width, height, glyphs }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn load(name: &str, bdf: &str) -> Result<Font, String> {
+        let path = std::env::temp_dir().join(format!("bitify-{}-{}.bdf", std::process::id(), name));
+        fs::write(&path, bdf).unwrap();
+        let font = load_bdf(&path);
+        fs::remove_file(&path).unwrap();
+        font
+    }
+
+    const TINY: &str = "STARTFONT 2.1
+FONTBOUNDINGBOX 4 4 0 -1
+STARTCHAR block
+ENCODING 9608
+BBX 2 2 1 0
+BITMAP
+C0
+40
+ENDCHAR
+ENDFONT
+";
+
+    #[test]
+    fn glyphs_are_placed_on_the_baseline() {
+        let font = load("tiny", TINY).unwrap();
+        assert_eq!(font.size(), (4, 4));
+        assert!(font.has_glyph('█') && !font.has_glyph('a'));
+        // Rows 1 and 2 of the cell hold the glyph; row 3 is below the baseline.
+        let glyph = font.glyph('█').unwrap();
+        assert_eq!(
+            glyph,
+            [false, false, false, false, false, true, true, false, false, false, true, false, false, false, false, false]
+        );
+        assert!(font.glyph('a').is_none());
+    }
+
+    #[test]
+    fn oversized_bounding_boxes_are_rejected() {
+        let error = load("huge", "FONTBOUNDINGBOX 100000 100000 0 0\n").err().unwrap();
+        assert!(error.contains("larger than 256"), "{}", error);
+        assert!(load("empty", "FONTBOUNDINGBOX 4 4 0 0\n").is_err());
+    }
+}
@@ -0,0 +1,251 @@
+use image::{DynamicImage, RgbImage};
+use std::collections::HashMap;
+use std::env;
+use std::fmt::Write as _;
+use std::io::Cursor;
+
+/// Terminal inline image protocols for previewing rendered PNGs.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Protocol {
+    Sixel,
+    Kitty,
+    Iterm,
+}
+
+/// `None` means detect from the environment. A named type keeps clap from
+/// treating `--protocol` as a flag with an optional value.
+pub type ProtocolChoice = Option<Protocol>;
+
+pub fn parse_protocol(s: &str) -> Result<ProtocolChoice, String> {
+    match s.to_lowercase().as_str() {
+        "auto" => Ok(None),
+        "sixel" => Ok(Some(Protocol::Sixel)),
+        "kitty" => Ok(Some(Protocol::Kitty)),
+        "iterm" | "iterm2" => Ok(Some(Protocol::Iterm)),
+        _ => Err(format!("Invalid protocol '{}'. Use: auto, sixel, kitty, iterm", s)),
+    }
+}
+
+/// Guesses the protocol from environment variables set by terminals that
+/// support one. Terminals are not queried, so this errs towards `None`.
+pub fn detect() -> Option<Protocol> {
+    let var = |name: &str| env::var(name).unwrap_or_default();
+    let term = var("TERM");
+    let program = var("TERM_PROGRAM");
+
+    if env::var_os("KITTY_WINDOW_ID").is_some() || term == "xterm-kitty" || term == "xterm-ghostty" || program == "ghostty" {
+        Some(Protocol::Kitty)
+    } else if program == "iTerm.app" || program == "WezTerm" || var("LC_TERMINAL") == "iTerm2" {
+        Some(Protocol::Iterm)
+    } else if term.contains("sixel") || term.starts_with("foot") || term.starts_with("mlterm") || env::var_os("KONSOLE_VERSION").is_some() {
+        Some(Protocol::Sixel)
+    } else {
+        None
+    }
+}
+
+const BASE64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+pub fn base64(bytes: &[u8]) -> String {
+    let mut out = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let n = chunk.iter().enumerate().fold(0u32, |n, (i, &b)| n | (b as u32) << (16 - 8 * i));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(BASE64[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+fn encode_png(img: &RgbImage) -> Result<Vec<u8>, image::ImageError> {
+    let mut buffer = Cursor::new(Vec::new());
+    DynamicImage::ImageRgb8(img.clone()).write_to(&mut buffer, image::ImageOutputFormat::Png)?;
+    Ok(buffer.into_inner())
+}
+
+/// Encodes `img` for `protocol`, followed by a newline so following output
+/// starts below the image.
+pub fn encode(img: &RgbImage, protocol: Protocol) -> Result<Vec<u8>, image::ImageError> {
+    let mut out = match protocol {
+        Protocol::Sixel => encode_sixel(img),
+        Protocol::Kitty => encode_kitty(&encode_png(img)?),
+        Protocol::Iterm => encode_iterm(&encode_png(img)?),
+    };
+    out.push(b'\n');
+    Ok(out)
+}
+
+/// Kitty graphics protocol: the PNG is sent base64-encoded in chunks of at
+/// most 4096 bytes, each flagged with whether more follow.
+pub fn encode_kitty(png: &[u8]) -> Vec<u8> {
+    let payload = base64(png);
+    let chunks: Vec<&[u8]> = payload.as_bytes().chunks(4096).collect();
+    let mut out = Vec::with_capacity(payload.len() + chunks.len() * 16);
+    for (i, chunk) in chunks.iter().enumerate() {
+        let more = (i + 1 < chunks.len()) as u8;
+        if i == 0 {
+            out.extend_from_slice(format!("\x1b_Ga=T,f=100,m={};", more).as_bytes());
+        } else {
+            out.extend_from_slice(format!("\x1b_Gm={};", more).as_bytes());
+        }
+        out.extend_from_slice(chunk);
+        out.extend_from_slice(b"\x1b\\");
+    }
+    out
+}
+
+/// iTerm2 inline images (also understood by WezTerm and mintty).
+pub fn encode_iterm(png: &[u8]) -> Vec<u8> {
+    format!("\x1b]1337;File=inline=1;size={};preserveAspectRatio=1:{}\x07", png.len(), base64(png)).into_bytes()
+}
+
+/// Maps every pixel to a sixel palette index. Images with at most 256
+/// distinct colors keep them exactly; others are reduced to a 6x6x6 cube.
+fn sixel_palette(img: &RgbImage) -> (Vec<[u8; 3]>, Vec<u8>) {
+    let mut palette = Vec::new();
+    let mut lookup = HashMap::new();
+    let mut indices = Vec::with_capacity((img.width() * img.height()) as usize);
+    for pixel in img.pixels() {
+        let index = *lookup.entry(pixel.0).or_insert_with(|| {
+            palette.push(pixel.0);
+            palette.len() - 1
+        });
+        if palette.len() > 256 {
+            break;
+        }
+        indices.push(index as u8);
+    }
+    if palette.len() <= 256 {
+        return (palette, indices);
+    }
+
+    let level = |c: u8| (c as u32 * 5 + 127) / 255;
+    let palette = (0..216u32)
+        .map(|i| [i / 36, i / 6 % 6, i % 6].map(|l| (l * 255 / 5) as u8))
+        .collect();
+    let indices = img
+        .pixels()
+        .map(|p| (level(p[0]) * 36 + level(p[1]) * 6 + level(p[2])) as u8)
+        .collect();
+    (palette, indices)
+}
+
+fn push_sixel_run(out: &mut String, sixel: u8, count: usize) {
+    let ch = (63 + sixel) as char;
+    if count > 3 {
+        let _ = write!(out, "!{}{}", count, ch);
+    } else {
+        out.extend(std::iter::repeat_n(ch, count));
+    }
+}
+
+/// DEC sixel graphics: the image is drawn in bands six pixels tall, one pass
+/// per palette color present in the band, with run-length compression.
+pub fn encode_sixel(img: &RgbImage) -> Vec<u8> {
+    let (width, height) = (img.width() as usize, img.height() as usize);
+    let (palette, indices) = sixel_palette(img);
+
+    let mut out = format!("\x1bPq\"1;1;{};{}", width, height);
+    for (i, [r, g, b]) in palette.iter().enumerate() {
+        let percent = |c: u8| (c as u32 * 100 + 127) / 255;
+        let _ = write!(out, "#{};2;{};{};{}", i, percent(*r), percent(*g), percent(*b));
+    }
+
+    for band in (0..height).step_by(6) {
+        let rows = (height - band).min(6);
+        let mut present = vec![false; palette.len()];
+        for y in band..band + rows {
+            for &index in &indices[y * width..(y + 1) * width] {
+                present[index as usize] = true;
+            }
+        }
+
+        let mut first = true;
+        for (color, _) in present.iter().enumerate().filter(|(_, present)| **present) {
+            if !first {
+                out.push('$');
+            }
+            first = false;
+            let _ = write!(out, "#{}", color);
+
+            let mut run = (0u8, 0usize);
+            for x in 0..width {
+                let sixel = (0..rows)
+                    .filter(|dy| indices[(band + dy) * width + x] as usize == color)
+                    .fold(0u8, |bits, dy| bits | 1 << dy);
+                if run.1 > 0 && run.0 != sixel {
+                    push_sixel_run(&mut out, run.0, run.1);
+                    run.1 = 0;
+                }
+                run = (sixel, run.1 + 1);
+            }
+            // Trailing empty columns need no output.
+            if run.0 != 0 {
+                push_sixel_run(&mut out, run.0, run.1);
+            }
+        }
+        out.push('-');
+    }
+    out.push_str("\x1b\\");
+    out.into_bytes()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::Rgb;
+
+    #[test]
+    fn base64_pads_partial_groups() {
+        assert_eq!(base64(b""), "");
+        assert_eq!(base64(b"f"), "Zg==");
+        assert_eq!(base64(b"fo"), "Zm8=");
+        assert_eq!(base64(b"foo"), "Zm9v");
+        assert_eq!(base64(b"foobar"), "Zm9vYmFy");
+    }
+
+    #[test]
+    fn sixel_frames_bands_in_dcs() {
+        // Red over blue: one band, one pass per color.
+        let img = RgbImage::from_fn(2, 2, |_, y| if y == 0 { Rgb([255, 0, 0]) } else { Rgb([0, 0, 255]) });
+        let out = String::from_utf8(encode_sixel(&img)).unwrap();
+        assert_eq!(out, "\x1bPq\"1;1;2;2#0;2;100;0;0#1;2;0;0;100#0@@$#1AA-\x1b\\");
+    }
+
+    #[test]
+    fn kitty_splits_payload_into_chunks() {
+        let png = vec![7u8; 5000];
+        let out = String::from_utf8(encode_kitty(&png)).unwrap();
+        let chunks: Vec<&str> = out.split_terminator("\x1b\\").collect();
+        assert_eq!(chunks.len(), 2);
+
+        let first = chunks[0].strip_prefix("\x1b_Ga=T,f=100,m=1;").unwrap();
+        let last = chunks[1].strip_prefix("\x1b_Gm=0;").unwrap();
+        assert_eq!(first.len(), 4096);
+        assert!(last.len() <= 4096);
+        assert_eq!(format!("{}{}", first, last), base64(&png));
+    }
+
+    #[test]
+    fn kitty_small_image_is_one_chunk() {
+        let out = String::from_utf8(encode_kitty(b"abc")).unwrap();
+        assert_eq!(out, "\x1b_Ga=T,f=100,m=0;YWJj\x1b\\");
+    }
+
+    #[test]
+    fn iterm_wraps_payload_in_osc_1337() {
+        let out = String::from_utf8(encode_iterm(b"abc")).unwrap();
+        assert_eq!(out, "\x1b]1337;File=inline=1;size=3;preserveAspectRatio=1:YWJj\x07");
+    }
+
+    #[test]
+    fn encode_ends_with_newline() {
+        let img = RgbImage::from_pixel(1, 1, Rgb([0, 0, 0]));
+        let out = encode(&img, Protocol::Iterm).unwrap();
+        assert!(out.starts_with(b"\x1b]1337;File=") && out.ends_with(b"\x07\n"));
+    }
+}
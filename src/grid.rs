@@ -0,0 +1,94 @@
+use crate::metadata::Metadata;
+use crate::{AsciiGrid, read_source};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// The `--format json` dump: the character grid plus the settings that
+/// produced it. Cells serialize as `{"char": "#", "rgb": [r, g, b]}`.
+#[derive(Serialize, Deserialize)]
+pub struct GridDocument {
+    pub width: usize,
+    pub height: usize,
+    /// Absent in grids that were not made by bitify.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub settings: Option<Metadata>,
+    pub cells: AsciiGrid,
+}
+
+pub fn is_grid_path(path: &str) -> bool {
+    Path::new(path)
+        .extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("json"))
+}
+
+impl GridDocument {
+    pub fn new(cells: AsciiGrid, settings: Metadata) -> GridDocument {
+        GridDocument {
+            width: cells.first().map_or(0, |row| row.len()),
+            height: cells.len(),
+            settings: Some(settings),
+            cells,
+        }
+    }
+
+    /// Reads a dump and checks that the cells match the stated dimensions.
+    pub fn read(path: &str) -> Result<GridDocument, Box<dyn std::error::Error>> {
+        let document: GridDocument = serde_json::from_slice(&read_source(path)?)
+            .map_err(|e| format!("Invalid grid {}: {}", path, e))?;
+
+        if document.width == 0 || document.height == 0 {
+            return Err(format!("Grid {} is empty", path).into());
+        }
+        if document.cells.len() != document.height {
+            return Err(format!("Grid {} has {} rows, expected {}", path, document.cells.len(), document.height).into());
+        }
+        if let Some((y, row)) = document.cells.iter().enumerate().find(|(_, row)| row.len() != document.width) {
+            return Err(format!("Grid {} row {} has {} cells, expected {}", path, y, row.len(), document.width).into());
+        }
+        Ok(document)
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::AsciiPixel;
+    use std::fs;
+
+    fn write_temp(name: &str, json: &str) -> String {
+        let path = std::env::temp_dir().join(format!("bitify-{}-{}.json", std::process::id(), name));
+        fs::write(&path, json).unwrap();
+        path.display().to_string()
+    }
+
+    #[test]
+    fn cells_serialize_as_char_and_rgb() {
+        let cell = AsciiPixel { character: '#', color: (1, 2, 3) };
+        assert_eq!(serde_json::to_string(&cell).unwrap(), r##"{"char":"#","rgb":[1,2,3]}"##);
+    }
+
+    #[test]
+    fn reads_grids_without_settings() {
+        let path = write_temp("plain", r#"{"width":2,"height":1,"cells":[[{"char":"a","rgb":[0,0,0]},{"char":"b","rgb":[9,9,9]}]]}"#);
+        let document = GridDocument::read(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert!(document.settings.is_none());
+        assert_eq!(document.cells[0][1].character, 'b');
+    }
+
+    #[test]
+    fn rejects_grids_that_do_not_match_their_size() {
+        for (name, json) in [
+            ("ragged", r#"{"width":2,"height":1,"cells":[[{"char":"a","rgb":[0,0,0]}]]}"#),
+            ("short", r#"{"width":1,"height":2,"cells":[[{"char":"a","rgb":[0,0,0]}]]}"#),
+            ("empty", r#"{"width":0,"height":0,"cells":[]}"#),
+        ] {
+            let path = write_temp(name, json);
+            let result = GridDocument::read(&path);
+            fs::remove_file(&path).unwrap();
+            assert!(result.is_err(), "{}", name);
+        }
+        assert!(is_grid_path("dump.JSON") && !is_grid_path("dump.png"));
+    }
+}
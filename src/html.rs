@@ -0,0 +1,86 @@
+use crate::palette::format_hex_color;
+use crate::{AsciiPixel, ConvertOptions};
+use std::fmt::Write as _;
+
+fn escape(ch: char, out: &mut String) {
+    match ch {
+        '&' => out.push_str("&amp;"),
+        '<' => out.push_str("&lt;"),
+        '>' => out.push_str("&gt;"),
+        '"' => out.push_str("&quot;"),
+        _ => out.push(ch),
+    }
+}
+
+/// Renders the grid as a `<pre>` block with one `<span>` per run of the
+/// same color. Spaces join the surrounding run since they show no color.
+pub fn render_pre(ascii_data: &[Vec<AsciiPixel>], options: &ConvertOptions) -> String {
+    let [r, g, b] = options.background().0;
+    let mut out = format!(
+        "<pre style=\"background:{};font-family:monospace;line-height:1;padding:1em\">",
+        format_hex_color((r, g, b))
+    );
+    for row in ascii_data {
+        let mut current = None;
+        for pixel in row {
+            if pixel.character != ' ' && current != Some(pixel.color) {
+                if current.is_some() {
+                    out.push_str("</span>");
+                }
+                let _ = write!(out, "<span style=\"color:{}\">", format_hex_color(pixel.color));
+                current = Some(pixel.color);
+            }
+            escape(pixel.character, &mut out);
+        }
+        if current.is_some() {
+            out.push_str("</span>");
+        }
+        out.push('\n');
+    }
+    out.push_str("</pre>");
+    out
+}
+
+/// A standalone HTML page around `render_pre`.
+pub fn render_page(ascii_data: &[Vec<AsciiPixel>], options: &ConvertOptions) -> String {
+    format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>bitify</title>\n</head>\n<body style=\"margin:0\">\n{}\n</body>\n</html>\n",
+        render_pre(ascii_data, options)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::DensityPreset;
+
+    fn pixel(character: char, color: (u8, u8, u8)) -> AsciiPixel {
+        AsciiPixel { character, color }
+    }
+
+    #[test]
+    fn spans_cover_runs_of_one_color() {
+        let red = (255, 0, 0);
+        let grid = vec![
+            vec![pixel('#', red), pixel(' ', (0, 0, 9)), pixel('@', red), pixel('%', (0, 0, 255))],
+            vec![pixel(' ', red)],
+        ];
+        let pre = render_pre(&grid, &ConvertOptions::new(DensityPreset::Medium));
+        assert_eq!(
+            pre,
+            "<pre style=\"background:#000000;font-family:monospace;line-height:1;padding:1em\">\
+             <span style=\"color:#ff0000\"># @</span><span style=\"color:#0000ff\">%</span>\n \n</pre>"
+        );
+    }
+
+    #[test]
+    fn markup_characters_are_escaped() {
+        let grid = vec![['<', '&', '>', '"'].map(|ch| pixel(ch, (1, 2, 3))).to_vec()];
+        let mut options = ConvertOptions::new(DensityPreset::Medium);
+        options.invert = true;
+        let page = render_page(&grid, &options);
+        assert!(page.contains("background:#ffffff"));
+        assert!(page.contains(">&lt;&amp;&gt;&quot;</span>"));
+        assert!(page.starts_with("<!DOCTYPE html>") && page.ends_with("</html>\n"));
+    }
+}
@@ -0,0 +1,226 @@
+//! Inputs the `image` crate cannot detect or decode on its own: SVG, HEIC,
+//! and AVIF files whose header its signature table misses.
+
+use image::{DynamicImage, ImageFormat};
+
+/// SVGs are rasterized at this many pixels per output column, the width of
+/// a built-in glyph, so rendered PNGs never sample below the vector's detail.
+#[cfg(feature = "svg")]
+const SVG_PIXELS_PER_COLUMN: u32 = 8;
+#[cfg(feature = "svg")]
+const SVG_MAX_SIDE: f32 = 8192.0;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Container {
+    Svg,
+    Heif,
+    Avif,
+}
+
+/// Recognizes SVG text and the ISO-BMFF brands of HEIF and AVIF files.
+pub fn sniff(bytes: &[u8]) -> Option<Container> {
+    if let Some(brands) = ftyp_brands(bytes) {
+        return if brands.iter().any(|&brand| matches!(brand, b"avif" | b"avis")) {
+            Some(Container::Avif)
+        } else if brands.iter().any(|&brand| matches!(brand, b"heic" | b"heix" | b"heim" | b"heis" | b"hevc" | b"hevx" | b"mif1" | b"msf1")) {
+            Some(Container::Heif)
+        } else {
+            None
+        };
+    }
+
+    let head = &bytes[..bytes.len().min(4096)];
+    let head = head.strip_prefix(b"\xef\xbb\xbf").unwrap_or(head);
+    let text = String::from_utf8_lossy(head);
+    let text = text.trim_start();
+    (text.starts_with('<') && text.contains("<svg")).then_some(Container::Svg)
+}
+
+/// The major and compatible brands of a leading `ftyp` box.
+fn ftyp_brands(bytes: &[u8]) -> Option<Vec<&[u8; 4]>> {
+    if bytes.get(4..8)? != b"ftyp" {
+        return None;
+    }
+    let size = u32::from_be_bytes(bytes[..4].try_into().ok()?) as usize;
+    let body = bytes.get(8..size.min(bytes.len()))?;
+    // Major brand, minor version, then compatible brands.
+    let compatible = body.get(8..).unwrap_or_default().chunks_exact(4);
+    Some(body.get(..4).into_iter().chain(compatible).filter_map(|brand| brand.try_into().ok()).collect())
+}
+
+/// The largest image size declared by the `ispe` (image spatial extents)
+/// properties of a HEIF or AVIF file. Every image item has one, so this
+/// bounds the primary image before anything is decoded.
+pub fn heif_dimensions(bytes: &[u8]) -> Option<(u32, u32)> {
+    let read = |at: usize| -> Option<u32> { Some(u32::from_be_bytes(bytes.get(at..at + 4)?.try_into().ok()?)) };
+    bytes
+        .windows(4)
+        .enumerate()
+        .filter(|&(at, window)| window == b"ispe" && at >= 4 && read(at - 4).is_some_and(|size| size >= 20))
+        // Box type, then version and flags, then width and height.
+        .filter_map(|(at, _)| Some((read(at + 8)?, read(at + 12)?)))
+        .max_by_key(|&(width, height)| width as u64 * height as u64)
+}
+
+#[cfg(not(feature = "svg"))]
+fn missing_feature(format: &str, feature: &str) -> Box<dyn std::error::Error> {
+    format!("{} input needs bitify built with the '{}' feature", format, feature).into()
+}
+
+/// Default builds leave out codecs that link a system library, so say how
+/// to get one rather than just which feature is missing.
+fn missing_system_codec(format: &str, feature: &str, library: &str) -> Box<dyn std::error::Error> {
+    format!(
+        "{} input needs bitify built with the '{}' feature (cargo build --features {}), which links the system {} library and is not in default builds",
+        format, feature, feature, library
+    )
+    .into()
+}
+
+/// Decodes a sniffed container. Returns whether EXIF orientation still
+/// needs applying: libheif already applies the HEIF transforms.
+pub fn decode(bytes: &[u8], container: Container, width: u32) -> Result<(DynamicImage, bool), Box<dyn std::error::Error>> {
+    match container {
+        Container::Svg => Ok((rasterize_svg(bytes, width)?, false)),
+        Container::Heif => Ok((decode_heif(bytes)?, false)),
+        Container::Avif if cfg!(feature = "avif") => {
+            let img = image::load_from_memory_with_format(bytes, ImageFormat::Avif)?;
+            Ok((img, true))
+        }
+        Container::Avif => Err(missing_system_codec("AVIF", "avif", "dav1d")),
+    }
+}
+
+/// Rasterizes an SVG `width` columns wide at `SVG_PIXELS_PER_COLUMN`,
+/// keeping its aspect ratio. Text uses the system fonts.
+#[cfg(feature = "svg")]
+fn rasterize_svg(bytes: &[u8], width: u32) -> Result<DynamicImage, Box<dyn std::error::Error>> {
+    use resvg::{tiny_skia, usvg};
+
+    let mut options = usvg::Options::default();
+    options.fontdb_mut().load_system_fonts();
+    let tree = usvg::Tree::from_data(bytes, &options)?;
+
+    let size = tree.size();
+    let scale = (width * SVG_PIXELS_PER_COLUMN) as f32 / size.width();
+    let scale = scale.min(SVG_MAX_SIDE / size.width()).min(SVG_MAX_SIDE / size.height());
+    let (pixel_width, pixel_height) = ((size.width() * scale).round().max(1.0), (size.height() * scale).round().max(1.0));
+    let mut pixmap = tiny_skia::Pixmap::new(pixel_width as u32, pixel_height as u32)
+        .ok_or("SVG has no drawable area")?;
+    resvg::render(&tree, tiny_skia::Transform::from_scale(scale, scale), &mut pixmap.as_mut());
+
+    let (pixel_width, pixel_height) = (pixmap.width(), pixmap.height());
+    let rgba = pixmap
+        .pixels()
+        .iter()
+        .flat_map(|pixel| {
+            let color = pixel.demultiply();
+            [color.red(), color.green(), color.blue(), color.alpha()]
+        })
+        .collect();
+    let img = image::RgbaImage::from_raw(pixel_width, pixel_height, rgba).ok_or("SVG raster has the wrong size")?;
+    Ok(DynamicImage::ImageRgba8(img))
+}
+
+#[cfg(not(feature = "svg"))]
+fn rasterize_svg(_bytes: &[u8], _width: u32) -> Result<DynamicImage, Box<dyn std::error::Error>> {
+    Err(missing_feature("SVG", "svg"))
+}
+
+#[cfg(feature = "heic")]
+fn decode_heif(bytes: &[u8]) -> Result<DynamicImage, Box<dyn std::error::Error>> {
+    use libheif_rs::{ColorSpace, HeifContext, LibHeif, RgbChroma};
+
+    let context = HeifContext::read_from_bytes(bytes)?;
+    let handle = context.primary_image_handle()?;
+    let image = LibHeif::new().decode(&handle, ColorSpace::Rgb(RgbChroma::Rgb), None)?;
+    let plane = image.planes().interleaved.ok_or("HEIF image has no interleaved plane")?;
+
+    let row_bytes = plane.width as usize * 3;
+    let rgb = plane
+        .data
+        .chunks(plane.stride)
+        .take(plane.height as usize)
+        .flat_map(|row| &row[..row_bytes])
+        .copied()
+        .collect();
+    let img = image::RgbImage::from_raw(plane.width, plane.height, rgb).ok_or("HEIF image has the wrong size")?;
+    Ok(DynamicImage::ImageRgb8(img))
+}
+
+#[cfg(not(feature = "heic"))]
+fn decode_heif(_bytes: &[u8]) -> Result<DynamicImage, Box<dyn std::error::Error>> {
+    Err(missing_system_codec("HEIC", "heic", "libheif (>= 1.17)"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An ISO-BMFF box of `kind` around `body`.
+    fn bmff_box(kind: &[u8; 4], body: &[u8]) -> Vec<u8> {
+        let mut bytes = ((body.len() + 8) as u32).to_be_bytes().to_vec();
+        bytes.extend(kind);
+        bytes.extend(body);
+        bytes
+    }
+
+    fn ispe(width: u32, height: u32) -> Vec<u8> {
+        let mut body = vec![0; 4];
+        body.extend(width.to_be_bytes());
+        body.extend(height.to_be_bytes());
+        bmff_box(b"ispe", &body)
+    }
+
+    #[test]
+    fn containers_are_sniffed_from_ftyp_brands() {
+        assert_eq!(sniff(&bmff_box(b"ftyp", b"avif\0\0\0\0mif1miaf")), Some(Container::Avif));
+        assert_eq!(sniff(&bmff_box(b"ftyp", b"msf1\0\0\0\0avis")), Some(Container::Avif));
+        assert_eq!(sniff(&bmff_box(b"ftyp", b"heic\0\0\0\0mif1")), Some(Container::Heif));
+        assert_eq!(sniff(&bmff_box(b"ftyp", b"mif1\0\0\0\0")), Some(Container::Heif));
+        // MP4 video shares the box format but is left to `image`.
+        assert_eq!(sniff(&bmff_box(b"ftyp", b"isom\0\0\0\0mp41")), None);
+        assert_eq!(sniff(b"\x89PNG\r\n\x1a\n"), None);
+    }
+
+    #[test]
+    fn ftyp_brands_stop_at_the_box_size() {
+        let mut bytes = bmff_box(b"ftyp", b"heic\0\0\0\0mif1");
+        bytes.extend(b"avifavif");
+        assert_eq!(ftyp_brands(&bytes), Some(vec![b"heic", b"mif1"]));
+        assert_eq!(ftyp_brands(&bytes[..14]), Some(vec![b"heic"]));
+        assert_eq!(ftyp_brands(b"\0\0\0\x08ftyp"), Some(vec![]));
+        assert_eq!(ftyp_brands(b"ftyp"), None);
+    }
+
+    #[test]
+    fn svg_is_sniffed_from_leading_markup() {
+        assert_eq!(sniff(b"<svg xmlns=\"http://www.w3.org/2000/svg\"/>"), Some(Container::Svg));
+        assert_eq!(sniff(b"\xef\xbb\xbf\n  <?xml version=\"1.0\"?>\n<svg/>"), Some(Container::Svg));
+        assert_eq!(sniff(b"<!DOCTYPE html><html></html>"), None);
+        assert_eq!(sniff(b"an <svg> in prose"), None);
+        // Only the first 4 KiB are searched for the root element.
+        let mut late = b"<?xml version=\"1.0\"?>".to_vec();
+        late.extend(vec![b' '; 4096]);
+        late.extend(b"<svg/>");
+        assert_eq!(sniff(&late), None);
+    }
+
+    #[test]
+    fn heif_dimensions_take_the_largest_item() {
+        let mut bytes = bmff_box(b"ftyp", b"heic\0\0\0\0mif1");
+        let mut properties = ispe(512, 512);
+        properties.extend(ispe(4032, 3024));
+        properties.extend(ispe(256, 256));
+        bytes.extend(bmff_box(b"ipco", &properties));
+        assert_eq!(heif_dimensions(&bytes), Some((4032, 3024)));
+    }
+
+    #[test]
+    fn heif_dimensions_need_a_complete_ispe_box() {
+        assert_eq!(heif_dimensions(&bmff_box(b"ftyp", b"avif\0\0\0\0")), None);
+        let truncated = &ispe(10, 10)[..14];
+        assert_eq!(heif_dimensions(truncated), None);
+        assert_eq!(heif_dimensions(b"\0\0\0\x02ispe\0\0\0\0\0\0\0\x01\0\0\0\x01"), None);
+    }
+}
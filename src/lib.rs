@@ -0,0 +1,722 @@
+//! Image to ASCII art conversion: the pipeline behind the `bitify` command,
+//! usable on its own to convert images and render or stream the result.
+
+use image::imageops::FilterType;
+use image::{DynamicImage, GenericImageView, Pixel, Rgb};
+use std::fs;
+use std::io::{self, Cursor, Read};
+use std::path::Path;
+
+pub mod ansi;
+pub mod crop;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+#[cfg(feature = "png-render")]
+pub mod font;
+#[cfg(feature = "terminal-color")]
+pub mod graphics;
+#[cfg(feature = "metadata")]
+pub mod grid;
+pub mod html;
+pub mod input;
+#[cfg(feature = "metadata")]
+pub mod metadata;
+pub mod orient;
+pub mod palette;
+#[cfg(feature = "png-render")]
+pub mod raster;
+#[cfg(feature = "png-render")]
+pub mod render;
+#[cfg(feature = "python")]
+mod python;
+pub mod tone;
+#[cfg(target_arch = "wasm32")]
+pub mod wasm;
+
+use crop::Crop;
+#[cfg(feature = "png-render")]
+use metadata::Metadata;
+#[cfg(feature = "png-render")]
+use raster::RasterOptions;
+use orient::{Flip, Rotation};
+use palette::ColorMode;
+use tone::{Adjustments, Luminance};
+
+#[cfg(feature = "png-render")]
+pub use render::{get_char_pattern, render_ascii_png, render_cells, rendered_size};
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum DensityPreset {
+    Low,
+    Medium, 
+    High,
+    Ultra,
+    Extreme,
+}
+
+pub fn parse_density(s: &str) -> Result<DensityPreset, String> {
+    match s.to_lowercase().as_str() {
+        "low" => Ok(DensityPreset::Low),
+        "medium" => Ok(DensityPreset::Medium),
+        "high" => Ok(DensityPreset::High),
+        "ultra" => Ok(DensityPreset::Ultra),
+        "extreme" => Ok(DensityPreset::Extreme),
+        _ => Err(format!("Invalid density '{}'. Use: low, medium, high, ultra, extreme", s)),
+    }
+}
+
+pub fn parse_charset(s: &str) -> Result<Vec<char>, String> {
+    let chars: Vec<char> = s.chars().filter(|c| !c.is_control()).collect();
+    if chars.len() < 2 {
+        return Err(format!("Charset '{}' needs at least two characters", s));
+    }
+    Ok(chars)
+}
+
+pub fn parse_filter(s: &str) -> Result<FilterType, String> {
+    match s.to_lowercase().as_str() {
+        "nearest" => Ok(FilterType::Nearest),
+        "triangle" | "bilinear" => Ok(FilterType::Triangle),
+        "catmullrom" | "catmull-rom" | "cubic" => Ok(FilterType::CatmullRom),
+        "gaussian" => Ok(FilterType::Gaussian),
+        "lanczos" | "lanczos3" => Ok(FilterType::Lanczos3),
+        _ => Err(format!("Invalid filter '{}'. Use: nearest, triangle, catmullrom, gaussian, lanczos", s)),
+    }
+}
+
+pub fn filter_name(filter: FilterType) -> &'static str {
+    match filter {
+        FilterType::Nearest => "nearest",
+        FilterType::Triangle => "triangle",
+        FilterType::CatmullRom => "catmullrom",
+        FilterType::Gaussian => "gaussian",
+        FilterType::Lanczos3 => "lanczos",
+    }
+}
+
+fn parse_finite(s: &str, name: &str) -> Result<f32, String> {
+    match s.parse::<f32>() {
+        Ok(value) if value.is_finite() => Ok(value),
+        _ => Err(format!("Invalid {} '{}'", name, s)),
+    }
+}
+
+pub fn parse_gamma(s: &str) -> Result<f32, String> {
+    check_gamma(parse_finite(s, "gamma")?)
+}
+
+pub fn parse_brightness(s: &str) -> Result<f32, String> {
+    check_brightness(parse_finite(s, "brightness")?)
+}
+
+pub fn parse_contrast(s: &str) -> Result<f32, String> {
+    check_contrast(parse_finite(s, "contrast")?)
+}
+
+/// The `check_*` functions validate values that did not come from text,
+/// such as config file numbers.
+pub fn check_gamma(gamma: f32) -> Result<f32, String> {
+    if !gamma.is_finite() || gamma <= 0.0 {
+        return Err(format!("Gamma must be a finite number greater than 0, got {}", gamma));
+    }
+    Ok(gamma)
+}
+
+pub fn check_brightness(brightness: f32) -> Result<f32, String> {
+    if !(-1.0..=1.0).contains(&brightness) {
+        return Err(format!("Brightness must be between -1.0 and 1.0, got {}", brightness));
+    }
+    Ok(brightness)
+}
+
+/// Negative contrast flips the ramp.
+pub fn check_contrast(contrast: f32) -> Result<f32, String> {
+    if !(-5.0..=5.0).contains(&contrast) {
+        return Err(format!("Contrast must be between -5.0 and 5.0, got {}", contrast));
+    }
+    Ok(contrast)
+}
+
+impl DensityPreset {
+    pub const ALL: [DensityPreset; 5] = [
+        DensityPreset::Low,
+        DensityPreset::Medium,
+        DensityPreset::High,
+        DensityPreset::Ultra,
+        DensityPreset::Extreme,
+    ];
+    
+    pub fn next(&self) -> DensityPreset {
+        let index = Self::ALL.iter().position(|d| d == self).unwrap_or(0);
+        Self::ALL[(index + 1) % Self::ALL.len()].clone()
+    }
+    
+    pub fn name(&self) -> &'static str {
+        match self {
+            DensityPreset::Low => "low",
+            DensityPreset::Medium => "medium",
+            DensityPreset::High => "high",
+            DensityPreset::Ultra => "ultra",
+            DensityPreset::Extreme => "extreme",
+        }
+    }
+    
+    pub fn get_chars(&self) -> &'static [char] {
+        match self {
+            DensityPreset::Low => &[' ', '.', ':', '+', '#', '@'],
+            DensityPreset::Medium => &[' ', '.', ':', '-', '=', '+', '*', '#', '%', '@'],
+            DensityPreset::High => &[' ', '.', '\'', '`', '^', '"', ',', ':', ';', 'I', 'l', '!', 'i', '>', '<', '~', '+', '_', '-', '?', ']', '[', '}', '{', '1', ')', '(', '|', '\\', '/', 't', 'f', 'j', 'r', 'x', 'n', 'u', 'v', 'c', 'z', 'X', 'Y', 'U', 'J', 'C', 'L', 'Q', '0', 'O', 'Z', 'm', 'w', 'q', 'p', 'd', 'b', 'k', 'h', 'a', 'o', '*', '#', 'M', 'W', '&', '8', '%', 'B', '@'],
+            DensityPreset::Ultra => &[' ', '.', '\'', '`', '^', '"', ',', ':', ';', 'I', 'l', '!', 'i', '>', '<', '~', '+', '_', '-', '?', ']', '[', '}', '{', '1', ')', '(', '|', '\\', '/', 't', 'f', 'j', 'r', 'x', 'n', 'u', 'v', 'c', 'z', 'X', 'Y', 'U', 'J', 'C', 'L', 'Q', '0', 'O', 'Z', 'm', 'w', 'q', 'p', 'd', 'b', 'k', 'h', 'a', 'o', '*', '#', 'M', 'W', '&', '8', '%', 'B', '@', '$'],
+            DensityPreset::Extreme => &[' ', '.', '\'', '`', '^', '"', ',', ':', ';', 'I', 'l', '!', 'i', '>', '<', '~', '+', '_', '-', '?', ']', '[', '}', '{', '1', ')', '(', '|', '\\', '/', 't', 'f', 'j', 'r', 'x', 'n', 'u', 'v', 'c', 'z', 'X', 'Y', 'U', 'J', 'C', 'L', 'Q', '0', 'O', 'Z', 'm', 'w', 'q', 'p', 'd', 'b', 'k', 'h', 'a', 'o', '*', '#', 'M', 'W', '&', '8', '%', 'B', '@', '$', 'A', 'G', 'H', 'K', 'P', 'R', 'S', 'T', 'V', 'g', 's', 'y', 'e', 'F', 'D', 'N', '2', '3', '4', '5', '6', '7', '9', 'E'],
+        }
+    }
+    
+    pub fn get_default_width(&self) -> u32 {
+        match self {
+            DensityPreset::Low => 40,
+            DensityPreset::Medium => 80,
+            DensityPreset::High => 120,
+            DensityPreset::Ultra => 150,
+            DensityPreset::Extreme => 200,
+        }
+    }
+}
+
+#[derive(Clone, serde::Serialize, serde::Deserialize)]
+pub struct AsciiPixel {
+    #[serde(rename = "char")]
+    pub character: char,
+    #[serde(rename = "rgb")]
+    pub color: (u8, u8, u8),
+}
+
+pub type AsciiGrid = Vec<Vec<AsciiPixel>>;
+
+/// Height over width of a built-in glyph cell.
+const CELL_ASPECT: f32 = 12.0 / 8.0;
+
+/// A fixed pixel size for rendered images. The grid is laid out with
+/// `width` columns and as many rows as keep the glyphs in proportion.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ImageSize {
+    Pixels(u32, u32),
+    /// The transformed source image's own size; see
+    /// `ConvertOptions::resolve_image_size`.
+    Source,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ConvertOptions {
+    pub width: u32,
+    pub density: DensityPreset,
+    pub charset: Option<Vec<char>>,
+    pub filter: FilterType,
+    pub luminance: Luminance,
+    pub adjustments: Adjustments,
+    pub invert: bool,
+    pub color: ColorMode,
+    pub palette: Option<Vec<(u8, u8, u8)>>,
+    pub exif: bool,
+    pub rotate: Option<Rotation>,
+    pub flip: Option<Flip>,
+    pub crop: Option<Crop>,
+    pub trim: Option<u8>,
+    /// Pixel size of one glyph in rendered images; `None` is the font's own.
+    pub cell_size: Option<(u32, u32)>,
+    /// Overrides `cell_size` with a fixed size for the whole image.
+    pub image_size: Option<ImageSize>,
+}
+
+impl ConvertOptions {
+    /// The command's defaults for `density`, at the preset's default width.
+    pub fn new(density: DensityPreset) -> ConvertOptions {
+        ConvertOptions {
+            width: density.get_default_width(),
+            density,
+            charset: None,
+            filter: FilterType::Nearest,
+            luminance: Luminance::Lstar,
+            adjustments: Adjustments::default(),
+            invert: false,
+            color: ColorMode::Truecolor,
+            palette: None,
+            exif: true,
+            rotate: None,
+            flip: None,
+            crop: None,
+            trim: None,
+            cell_size: None,
+            image_size: None,
+        }
+    }
+    
+    pub fn chars(&self) -> &[char] {
+        self.charset.as_deref().unwrap_or_else(|| self.density.get_chars())
+    }
+    
+    pub fn glyph_color(&self, color: (u8, u8, u8)) -> (u8, u8, u8) {
+        match &self.palette {
+            Some(palette) if self.color != ColorMode::Mono => palette::nearest_in_palette(palette, color),
+            _ => self.color.apply(color, self.invert),
+        }
+    }
+    
+    pub fn background(&self) -> Rgb<u8> {
+        if self.invert {
+            Rgb([255, 255, 255])
+        } else {
+            Rgb([0, 0, 0])
+        }
+    }
+    
+    /// Turns `ImageSize::Source` into the size of `img`, which should be
+    /// the image about to be converted. Rendering needs the resolved size.
+    pub fn resolve_image_size(&mut self, img: &DynamicImage) {
+        if self.image_size == Some(ImageSize::Source) {
+            let (width, height) = img.dimensions();
+            self.image_size = Some(ImageSize::Pixels(width, height));
+        }
+    }
+    
+    /// Command-line flags that reproduce these options; defaults are omitted.
+    pub fn to_args(&self) -> Vec<String> {
+        let mut args = Vec::new();
+        let defaults = Adjustments::default();
+        // Values are joined to their flags so that ones starting with `-`
+        // (a negative contrast, a ramp) are not read as flags.
+        let mut push = |flag: &str, value: Option<String>| match value {
+            Some(value) => args.push(format!("{}={}", flag, value)),
+            None => args.push(flag.to_string()),
+        };
+        
+        push("--density", Some(self.density.name().to_string()));
+        push("--width", Some(self.width.to_string()));
+        if let Some(charset) = &self.charset {
+            push("--charset", Some(charset.iter().collect()));
+        }
+        if self.filter != FilterType::Nearest {
+            push("--filter", Some(filter_name(self.filter).to_string()));
+        }
+        if self.luminance != Luminance::Lstar {
+            push("--luminance", Some(self.luminance.name().to_string()));
+        }
+        if self.adjustments.brightness != defaults.brightness {
+            push("--brightness", Some(self.adjustments.brightness.to_string()));
+        }
+        if self.adjustments.contrast != defaults.contrast {
+            push("--contrast", Some(self.adjustments.contrast.to_string()));
+        }
+        if self.adjustments.gamma != defaults.gamma {
+            push("--gamma", Some(self.adjustments.gamma.to_string()));
+        }
+        if self.adjustments.auto_levels {
+            push("--auto-levels", None);
+        }
+        if self.adjustments.equalize {
+            push("--equalize", None);
+        }
+        if self.invert {
+            push("--invert", None);
+        }
+        if self.color != ColorMode::Truecolor {
+            push("--color", Some(self.color.name().to_string()));
+        }
+        if let Some(palette) = &self.palette {
+            let colors: Vec<String> = palette.iter().map(|&c| palette::format_hex_color(c)).collect();
+            push("--palette", Some(colors.join(",")));
+        }
+        if !self.exif {
+            push("--no-exif", None);
+        }
+        if let Some(rotation) = self.rotate {
+            push("--rotate", Some(rotation.name().to_string()));
+        }
+        if let Some(flip) = self.flip {
+            push("--flip", Some(flip.name().to_string()));
+        }
+        if let Some(crop) = &self.crop {
+            push("--crop", Some(crop.to_string()));
+        }
+        if let Some(tolerance) = self.trim {
+            push("--trim", None);
+            push("--trim-tolerance", Some(tolerance.to_string()));
+        }
+        if let Some((width, height)) = self.cell_size {
+            push("--cell-size", Some(format!("{}x{}", width, height)));
+        }
+        match self.image_size {
+            Some(ImageSize::Pixels(width, height)) => push("--png-size", Some(format!("{}x{}", width, height))),
+            Some(ImageSize::Source) => push("--match-source", None),
+            None => {}
+        }
+        args
+    }
+}
+
+pub fn read_source(image_path: &str) -> io::Result<Vec<u8>> {
+    if image_path == "-" {
+        let mut bytes = Vec::new();
+        io::stdin().lock().read_to_end(&mut bytes)?;
+        Ok(bytes)
+    } else {
+        fs::read(image_path)
+    }
+}
+
+pub fn load_image(image_path: &str, options: &ConvertOptions) -> Result<DynamicImage, Box<dyn std::error::Error>> {
+    let bytes = read_source(image_path)?;
+    let format = match image_path {
+        "-" => None,
+        path => image::ImageFormat::from_path(path).ok(),
+    };
+    decode_image(&bytes, format, options)
+}
+
+/// Decodes encoded image bytes and applies EXIF orientation and the
+/// transforms in `options`. `format` is a hint (e.g. from a file extension);
+/// the bytes are sniffed when it is wrong or missing. SVGs are rasterized
+/// to suit `options.width`.
+pub fn decode_image(bytes: &[u8], format: Option<image::ImageFormat>, options: &ConvertOptions) -> Result<DynamicImage, Box<dyn std::error::Error>> {
+    let (mut img, orient) = match input::sniff(bytes) {
+        Some(container) => input::decode(bytes, container, options.width)?,
+        None => {
+            let mut reader = image::io::Reader::new(Cursor::new(bytes));
+            if let Some(format) = format {
+                reader.set_format(format);
+            }
+            (reader.with_guessed_format()?.decode()?, true)
+        }
+    };
+    
+    if orient && options.exif && let Some(orientation) = orient::read_exif_orientation(bytes) {
+        img = orient::apply_exif_orientation(img, orientation);
+    }
+    
+    transform_image(img, options)
+}
+
+pub fn transform_image(mut img: DynamicImage, options: &ConvertOptions) -> Result<DynamicImage, Box<dyn std::error::Error>> {
+    if let Some(rotation) = options.rotate {
+        img = orient::rotate(img, rotation);
+    }
+    if let Some(flip) = options.flip {
+        img = orient::flip(img, flip);
+    }
+    if let Some(crop) = &options.crop {
+        img = crop.apply(&img)?;
+    }
+    if let Some(tolerance) = options.trim {
+        img = crop::trim(&img, tolerance);
+    }
+    
+    Ok(img)
+}
+
+pub fn process_image(image_path: &str, options: &ConvertOptions) -> Result<AsciiGrid, Box<dyn std::error::Error>> {
+    let img = load_image(image_path, options)?;
+    convert_image(&img, options)
+}
+
+/// Converts encoded image bytes, e.g. an upload or a buffer from another
+/// language; the in-memory counterpart of `process_image`.
+pub fn convert_bytes(bytes: &[u8], options: &ConvertOptions) -> Result<AsciiGrid, Box<dyn std::error::Error>> {
+    let img = decode_image(bytes, None, options)?;
+    convert_image(&img, options)
+}
+
+pub fn convert_image(img: &DynamicImage, options: &ConvertOptions) -> Result<AsciiGrid, Box<dyn std::error::Error>> {
+    Ok(convert_rows(img, options)?.collect())
+}
+
+/// Converted rows, produced one at a time so large conversions can be
+/// streamed (see `ansi::write_rows`) without holding the whole grid.
+pub struct Rows<'a> {
+    resized: DynamicImage,
+    levels: Vec<f32>,
+    options: &'a ConvertOptions,
+    y: u32,
+}
+
+/// Fails if `options.width` is 0.
+pub fn convert_rows<'a>(img: &DynamicImage, options: &'a ConvertOptions) -> Result<Rows<'a>, Box<dyn std::error::Error>> {
+    let (width, height) = img.dimensions();
+    let target_width = options.width;
+    if target_width == 0 {
+        return Err("Width must be at least 1".into());
+    }
+    
+    let resized = match options.image_size {
+        Some(size) => {
+            let (image_width, image_height) = match size {
+                ImageSize::Pixels(image_width, image_height) => (image_width, image_height),
+                ImageSize::Source => (width, height),
+            };
+            // Square pixels need glyph-shaped cells; the source is
+            // center-cropped to the image's proportions.
+            let columns = target_width.min(image_width);
+            let cell_height = image_width as f32 / columns as f32 * CELL_ASPECT;
+            let rows = ((image_height as f32 / cell_height).round() as u32).clamp(1, image_height);
+            let (crop_width, crop_height) = if width as u64 * image_height as u64 > height as u64 * image_width as u64 {
+                ((height as u64 * image_width as u64 / image_height as u64).max(1) as u32, height)
+            } else {
+                (width, (width as u64 * image_height as u64 / image_width as u64).max(1) as u32)
+            };
+            img.crop_imm((width - crop_width) / 2, (height - crop_height) / 2, crop_width, crop_height)
+                .resize_exact(columns, rows, options.filter)
+        }
+        None => {
+            let aspect_ratio = height as f32 / width as f32;
+            let target_height = ((target_width as f32 * aspect_ratio * 0.5) as u32).max(1);
+            img.resize_exact(target_width, target_height, options.filter)
+        }
+    };
+    
+    // Levels need the whole image for auto-levels and equalization.
+    let mut levels: Vec<f32> = resized
+        .pixels()
+        .map(|(_, _, pixel)| {
+            let rgba = pixel.to_rgba();
+            options.luminance.level(rgba[0], rgba[1], rgba[2])
+        })
+        .collect();
+    options.adjustments.apply(&mut levels);
+    
+    Ok(Rows { resized, levels, options, y: 0 })
+}
+
+impl Iterator for Rows<'_> {
+    type Item = Vec<AsciiPixel>;
+    
+    fn next(&mut self) -> Option<Vec<AsciiPixel>> {
+        let (target_width, target_height) = self.resized.dimensions();
+        if self.y >= target_height {
+            return None;
+        }
+        let y = self.y;
+        self.y += 1;
+        
+        let options = self.options;
+        let ascii_chars = options.chars();
+        let mut row = Vec::with_capacity(target_width as usize);
+        for x in 0..target_width {
+            let pixel = self.resized.get_pixel(x, y);
+            let rgba = pixel.to_rgba();
+            
+            let mut brightness = self.levels[(y * target_width + x) as usize];
+            let mut color = (rgba[0], rgba[1], rgba[2]);
+            if options.invert {
+                brightness = 1.0 - brightness;
+                color = darken_for_light_background(color);
+            }
+            
+            let char_index = (brightness * (ascii_chars.len() - 1) as f32) as usize;
+            
+            row.push(AsciiPixel {
+                character: ascii_chars[char_index],
+                color: options.glyph_color(color),
+            });
+        }
+        Some(row)
+    }
+    
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = self.resized.height().saturating_sub(self.y) as usize;
+        (remaining, Some(remaining))
+    }
+}
+
+fn darken_for_light_background(color: (u8, u8, u8)) -> (u8, u8, u8) {
+    const MAX_LIGHTNESS: f32 = 0.55;
+    let lightness = Luminance::Lstar.level(color.0, color.1, color.2);
+    if lightness <= MAX_LIGHTNESS {
+        return color;
+    }
+    let scale = MAX_LIGHTNESS / lightness;
+    let darken = |c: u8| (c as f32 * scale) as u8;
+    (darken(color.0), darken(color.1), darken(color.2))
+}
+
+/// File name stem for outputs derived from `path`; stdin is `stdin`.
+pub fn source_stem(path: &str) -> &str {
+    if path == "-" {
+        "stdin"
+    } else {
+        Path::new(path).file_stem().and_then(|s| s.to_str()).unwrap_or("image")
+    }
+}
+
+#[cfg(feature = "png-render")]
+pub fn save_ascii_png(ascii_data: &[Vec<AsciiPixel>], original_path: &str, output_dir: &Path, options: &ConvertOptions) -> Result<std::path::PathBuf, Box<dyn std::error::Error>> {
+    save_ascii_image(ascii_data, original_path, output_dir, options, &RasterOptions::default())
+}
+
+/// Saves `{stem}_{density}_ascii.{extension}` in `output_dir`.
+#[cfg(feature = "png-render")]
+pub fn save_ascii_image(ascii_data: &[Vec<AsciiPixel>], original_path: &str, output_dir: &Path, options: &ConvertOptions, raster: &RasterOptions) -> Result<std::path::PathBuf, Box<dyn std::error::Error>> {
+    fs::create_dir_all(output_dir)?;
+    
+    let output_path = output_dir.join(format!("{}_{:?}_ascii.{}", source_stem(original_path), options.density, raster.format.extension()));
+    
+    fs::write(&output_path, encode_ascii_image(ascii_data, original_path, options, raster)?)?;
+    Ok(output_path)
+}
+
+/// Writes the rendered PNG with the conversion settings embedded so that
+/// `bitify reproduce` can regenerate it.
+#[cfg(feature = "png-render")]
+pub fn write_ascii_png(ascii_data: &[Vec<AsciiPixel>], original_path: &str, output_path: &Path, options: &ConvertOptions) -> Result<(), Box<dyn std::error::Error>> {
+    fs::write(output_path, encode_ascii_png(ascii_data, original_path, options)?)?;
+    Ok(())
+}
+
+/// The PNG `write_ascii_png` writes, as bytes. `source` is recorded in the
+/// metadata; pass `-` for images that did not come from a file.
+#[cfg(feature = "png-render")]
+pub fn encode_ascii_png(ascii_data: &[Vec<AsciiPixel>], source: &str, options: &ConvertOptions) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    encode_ascii_image(ascii_data, source, options, &RasterOptions::default())
+}
+
+/// Renders and encodes the grid as `raster.format`.
+#[cfg(feature = "png-render")]
+pub fn encode_ascii_image(ascii_data: &[Vec<AsciiPixel>], source: &str, options: &ConvertOptions, raster: &RasterOptions) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    raster::encode(&render_ascii_png(ascii_data, options), raster, &Metadata::new(source, options))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn adjustment_parsers_reject_non_finite_and_out_of_range_values() {
+        assert_eq!(parse_gamma("1.4"), Ok(1.4));
+        for bad in ["0", "-1", "NaN", "inf", "x"] {
+            assert!(parse_gamma(bad).is_err(), "gamma {}", bad);
+        }
+        assert_eq!(parse_brightness("-1"), Ok(-1.0));
+        for bad in ["1.5", "-1.01", "NaN", "-inf"] {
+            assert!(parse_brightness(bad).is_err(), "brightness {}", bad);
+        }
+        assert_eq!(parse_contrast("-0.5"), Ok(-0.5));
+        for bad in ["6", "NaN", "inf"] {
+            assert!(parse_contrast(bad).is_err(), "contrast {}", bad);
+        }
+        assert!(check_gamma(f32::NAN).is_err());
+    }
+
+    fn solid(width: u32, height: u32, color: [u8; 3]) -> DynamicImage {
+        DynamicImage::ImageRgb8(image::RgbImage::from_pixel(width, height, Rgb(color)))
+    }
+
+    #[test]
+    fn invert_swaps_the_ramp_and_background() {
+        let mut options = ConvertOptions::new(DensityPreset::Low);
+        options.width = 4;
+        let white = solid(8, 8, [255, 255, 255]);
+        let black = solid(8, 8, [0, 0, 0]);
+        assert_eq!(convert_image(&white, &options).unwrap()[0][0].character, '@');
+
+        options.invert = true;
+        assert_eq!(convert_image(&white, &options).unwrap()[0][0].character, ' ');
+        assert_eq!(convert_image(&black, &options).unwrap()[0][0].character, '@');
+        assert_eq!(options.background(), Rgb([255, 255, 255]));
+    }
+
+    #[test]
+    fn light_colors_are_darkened_for_white_backgrounds() {
+        let (r, g, b) = darken_for_light_background((250, 240, 100));
+        assert!(Luminance::Lstar.level(r, g, b) < 0.6);
+        assert!(r > g && g > b);
+        assert_eq!(darken_for_light_background((40, 0, 80)), (40, 0, 80));
+    }
+
+    #[test]
+    fn bytes_are_decoded_without_a_format_hint() {
+        let mut options = ConvertOptions::new(DensityPreset::Low);
+        options.width = 2;
+        let grid = convert_bytes(b"P6\n2 2\n255\n\xff\xff\xff\xff\xff\xff\xff\xff\xff\xff\xff\xff", &options).unwrap();
+        assert_eq!(grid.len(), 1);
+        assert_eq!(grid[0].len(), 2);
+        assert_eq!(grid[0][0].character, '@');
+        assert!(convert_bytes(b"not an image", &options).is_err());
+    }
+
+    #[test]
+    fn zero_width_is_rejected() {
+        let mut options = ConvertOptions::new(DensityPreset::Low);
+        options.width = 0;
+        assert!(convert_image(&solid(4, 4, [9, 9, 9]), &options).is_err());
+        options.width = 1;
+        assert_eq!(convert_image(&solid(4, 4, [9, 9, 9]), &options).unwrap().len(), 1);
+    }
+
+    /// Red, blue and green thirds, left to right.
+    fn stripes() -> DynamicImage {
+        DynamicImage::ImageRgb8(image::RgbImage::from_fn(300, 100, |x, _| match x / 100 {
+            0 => Rgb([255, 0, 0]),
+            1 => Rgb([0, 0, 255]),
+            _ => Rgb([0, 255, 0]),
+        }))
+    }
+
+    #[test]
+    fn image_sizes_pick_rows_for_glyph_shaped_cells() {
+        let mut options = ConvertOptions::new(DensityPreset::Low);
+        options.width = 80;
+        options.image_size = Some(ImageSize::Pixels(1920, 1080));
+        let grid = convert_image(&stripes(), &options).unwrap();
+        // 24x36 pixel cells fill 1080 rows exactly.
+        assert_eq!((grid[0].len(), grid.len()), (80, 30));
+
+        // Never more columns than pixels.
+        options.image_size = Some(ImageSize::Pixels(6, 12));
+        let grid = convert_image(&stripes(), &options).unwrap();
+        assert_eq!((grid[0].len(), grid.len()), (6, 8));
+    }
+
+    #[test]
+    fn sources_are_center_cropped_to_the_image_size() {
+        let mut options = ConvertOptions::new(DensityPreset::Low);
+        options.width = 10;
+        options.image_size = Some(ImageSize::Pixels(100, 100));
+        let grid = convert_image(&stripes(), &options).unwrap();
+        assert_eq!((grid[0].len(), grid.len()), (10, 7));
+        assert!(grid.iter().flatten().all(|pixel| pixel.color == (0, 0, 255)));
+
+        options.image_size = Some(ImageSize::Source);
+        options.resolve_image_size(&stripes());
+        assert_eq!(options.image_size, Some(ImageSize::Pixels(300, 100)));
+        let grid = convert_image(&stripes(), &options).unwrap();
+        assert_eq!(grid[0].first().map(|pixel| pixel.color), Some((255, 0, 0)));
+        assert_eq!(grid[0].last().map(|pixel| pixel.color), Some((0, 255, 0)));
+    }
+
+    #[test]
+    fn stdin_sources_are_named_stdin() {
+        assert_eq!(source_stem("-"), "stdin");
+        assert_eq!(source_stem("photos/cat.jpeg"), "cat");
+    }
+
+    #[test]
+    fn streamed_rows_match_the_whole_grid() {
+        let img = DynamicImage::ImageRgb8(image::RgbImage::from_fn(37, 23, |x, y| Rgb([(x * 7) as u8, (y * 11) as u8, (x * y) as u8])));
+        let mut options = ConvertOptions::new(DensityPreset::High);
+        options.width = 19;
+        options.adjustments.equalize = true;
+
+        let cells = |rows: &[Vec<AsciiPixel>]| -> Vec<(char, (u8, u8, u8))> {
+            rows.iter().flatten().map(|pixel| (pixel.character, pixel.color)).collect()
+        };
+        let rows = convert_rows(&img, &options).unwrap();
+        assert_eq!(rows.size_hint(), (5, Some(5)));
+        let streamed: AsciiGrid = rows.collect();
+        let whole = convert_image(&img, &options).unwrap();
+        assert_eq!(streamed.len(), whole.len());
+        assert_eq!(cells(&streamed), cells(&whole));
+
+        let mut from_rows = Vec::new();
+        ansi::write_rows(&mut from_rows, convert_rows(&img, &options).unwrap(), ColorMode::Truecolor, 0).unwrap();
+        let mut from_grid = Vec::new();
+        ansi::write_rows(&mut from_grid, whole, ColorMode::Truecolor, 0).unwrap();
+        assert_eq!(from_rows, from_grid);
+    }
+}
@@ -0,0 +1,273 @@
+use crate::play::{TerminalGuard, draw_frame};
+use crate::video::{FrameRate, read_ppm_frame};
+use bitify::{AsciiGrid, ConvertOptions, convert_image, transform_image};
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use crossterm::style::{Print, ResetColor};
+use crossterm::{cursor, queue, terminal};
+use image::{DynamicImage, RgbImage};
+use std::io::{self, BufReader, IsTerminal, Read, Write};
+use std::process::{Child, ChildStderr, ChildStdout, Command, Stdio};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+const MIN_FPS: f64 = 1.0;
+const MAX_FPS: f64 = 60.0;
+
+pub enum LiveSource {
+    /// A V4L2 capture device such as `/dev/video0`, read through ffmpeg.
+    Device(String),
+    /// Raw `rgb24` frames of a fixed size on stdin, e.g. from
+    /// `ffmpeg -f rawvideo -pix_fmt rgb24 -`.
+    RawStdin { width: u32, height: u32 },
+}
+
+#[derive(Default)]
+struct Latest {
+    frame: Option<RgbImage>,
+    sequence: u64,
+    ended: Option<Result<(), String>>,
+}
+
+type SharedLatest = Arc<Mutex<Latest>>;
+type SharedChild = Arc<Mutex<Child>>;
+
+fn read_raw_frame(reader: &mut impl Read, width: u32, height: u32) -> io::Result<Option<RgbImage>> {
+    let mut pixels = vec![0u8; width as usize * height as usize * 3];
+    let mut filled = 0;
+    while filled < pixels.len() {
+        match reader.read(&mut pixels[filled..])? {
+            0 if filled == 0 => return Ok(None),
+            0 => return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "truncated raw frame on stdin")),
+            n => filled += n,
+        }
+    }
+    Ok(RgbImage::from_raw(width, height, pixels))
+}
+
+type DeviceReader = (Child, BufReader<ChildStdout>, ChildStderr);
+
+fn spawn_device_reader(device: &str, rate: FrameRate) -> Result<DeviceReader, Box<dyn std::error::Error>> {
+    let mut child = Command::new("ffmpeg")
+        .args(["-v", "error", "-nostdin", "-f", "v4l2"])
+        .args(["-framerate", &format!("{}/{}", rate.num, rate.den)])
+        .args(["-i", device])
+        .args(["-f", "image2pipe", "-c:v", "ppm", "-pix_fmt", "rgb24", "-"])
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("Could not run ffmpeg ({}); is it installed?", e))?;
+    let stdout = BufReader::new(child.stdout.take().ok_or("ffmpeg stdout unavailable")?);
+    let stderr = child.stderr.take().ok_or("ffmpeg stderr unavailable")?;
+    Ok((child, stdout, stderr))
+}
+
+/// Why ffmpeg stopped, if it failed: its exit status and last error line
+/// (e.g. a missing or busy device).
+fn ffmpeg_failure(child: &Mutex<Child>, stderr: String) -> Option<String> {
+    let status = child.lock().unwrap().wait().ok()?;
+    if status.success() {
+        return None;
+    }
+    Some(match stderr.lines().rev().find(|line| !line.trim().is_empty()) {
+        Some(line) => format!("ffmpeg exited with {}: {}", status, line.trim()),
+        None => format!("ffmpeg exited with {}", status),
+    })
+}
+
+/// Reads frames on a background thread and keeps only the newest one, so a
+/// slow terminal drops frames instead of falling behind the camera.
+fn start_reader(source: &LiveSource, rate: FrameRate) -> Result<(SharedLatest, Option<SharedChild>), Box<dyn std::error::Error>> {
+    let latest = Arc::new(Mutex::new(Latest::default()));
+    let shared = Arc::clone(&latest);
+
+    let publish = move |frame: Result<Option<RgbImage>, String>| -> bool {
+        let mut latest = shared.lock().unwrap();
+        match frame {
+            Ok(Some(frame)) => {
+                latest.frame = Some(frame);
+                latest.sequence += 1;
+                true
+            }
+            Ok(None) => {
+                latest.ended = Some(Ok(()));
+                false
+            }
+            Err(e) => {
+                latest.ended = Some(Err(e));
+                false
+            }
+        }
+    };
+
+    match *source {
+        LiveSource::Device(ref device) => {
+            let (child, mut stdout, mut stderr) = spawn_device_reader(device, rate)?;
+            let child = Arc::new(Mutex::new(child));
+            let waiter = Arc::clone(&child);
+            // Drained alongside stdout so a chatty ffmpeg cannot block on a full pipe.
+            let errors = thread::spawn(move || {
+                let mut text = String::new();
+                let _ = stderr.read_to_string(&mut text);
+                text
+            });
+            thread::spawn(move || {
+                let end = loop {
+                    match read_ppm_frame(&mut stdout) {
+                        Ok(Some(frame)) => {
+                            publish(Ok(Some(frame)));
+                        }
+                        Ok(None) => break Ok(None),
+                        Err(e) => break Err(e.to_string()),
+                    }
+                };
+                let errors = errors.join().unwrap_or_default();
+                publish(ffmpeg_failure(&waiter, errors).map_or(end, Err));
+            });
+            Ok((latest, Some(child)))
+        }
+        LiveSource::RawStdin { width, height } => {
+            thread::spawn(move || {
+                let mut stdin = io::stdin().lock();
+                while publish(read_raw_frame(&mut stdin, width, height).map_err(|e| e.to_string())) {}
+            });
+            Ok((latest, None))
+        }
+    }
+}
+
+fn draw_status(out: &mut impl Write, row: usize, fps: f64, paused: bool, ended: bool) -> io::Result<()> {
+    let state = if ended {
+        "ended"
+    } else if paused {
+        "paused"
+    } else {
+        "live"
+    };
+    queue!(
+        out,
+        cursor::MoveTo(0, row as u16),
+        ResetColor,
+        terminal::Clear(terminal::ClearType::CurrentLine),
+        Print(format!("[{}] {:.0} fps  +/-: frame rate  space: pause  q: quit", state, fps)),
+    )
+}
+
+/// Continuously converts frames from a camera or a raw stdin stream into
+/// terminal ASCII at `fps`, adjustable while running.
+pub fn live(source: &LiveSource, fps: FrameRate, options: &ConvertOptions) -> Result<(), Box<dyn std::error::Error>> {
+    if !io::stdout().is_terminal() {
+        return Err("live needs an interactive terminal".into());
+    }
+
+    let mut options = options.clone();
+    let (columns, rows) = terminal::size()?;
+    options.width = options.width.min(columns as u32);
+    let max_rows = (rows as usize).saturating_sub(1);
+
+    let (latest, child) = start_reader(source, fps)?;
+
+    let result = (|| -> Result<(), Box<dyn std::error::Error>> {
+        let _guard = TerminalGuard::enter()?;
+        let mut stdout = io::stdout().lock();
+
+        let mut fps = fps.as_f64().clamp(MIN_FPS, MAX_FPS);
+        let mut paused = false;
+        let mut ended = false;
+        let mut shown_sequence = 0;
+        let mut previous: Option<AsciiGrid> = None;
+        let mut status_row = 0;
+        let mut next_tick = Instant::now();
+
+        loop {
+            let timeout = next_tick.saturating_duration_since(Instant::now());
+            if event::poll(timeout)? {
+                if let Event::Key(KeyEvent { code, modifiers, kind, .. }) = event::read()?
+                    && kind != KeyEventKind::Release
+                {
+                    match code {
+                        KeyCode::Char('c') if modifiers.contains(KeyModifiers::CONTROL) => break,
+                        KeyCode::Char('q') | KeyCode::Esc => break,
+                        KeyCode::Char(' ') => paused = !paused,
+                        KeyCode::Char('+') | KeyCode::Char('=') | KeyCode::Up => fps = (fps + 1.0).min(MAX_FPS),
+                        KeyCode::Char('-') | KeyCode::Down => fps = (fps - 1.0).max(MIN_FPS),
+                        _ => {}
+                    }
+                    draw_status(&mut stdout, status_row, fps, paused, ended)?;
+                    stdout.flush()?;
+                }
+                continue;
+            }
+            next_tick = Instant::now() + Duration::from_secs_f64(1.0 / fps);
+
+            let frame = {
+                let mut latest = latest.lock().unwrap();
+                if let Some(result) = latest.ended.take() {
+                    result?;
+                    ended = true;
+                    draw_status(&mut stdout, status_row, fps, paused, ended)?;
+                    stdout.flush()?;
+                }
+                if paused || latest.sequence == shown_sequence {
+                    continue;
+                }
+                shown_sequence = latest.sequence;
+                latest.frame.take()
+            };
+            let Some(frame) = frame else { continue };
+
+            let img = transform_image(DynamicImage::ImageRgb8(frame), &options)?;
+            let grid = convert_image(&img, &options)?;
+            draw_frame(&mut stdout, &grid, previous.as_ref(), max_rows, options.color)?;
+            status_row = grid.len().min(max_rows);
+            draw_status(&mut stdout, status_row, fps, paused, ended)?;
+            stdout.flush()?;
+            previous = Some(grid);
+        }
+        Ok(())
+    })();
+
+    if let Some(child) = &child {
+        let mut child = child.lock().unwrap();
+        let _ = child.kill();
+        let _ = child.wait();
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bitify::DensityPreset;
+    use std::io::Cursor;
+
+    #[test]
+    fn raw_frames_convert_to_grids() {
+        let (width, height) = (4, 2);
+        let frame_bytes = (width * height * 3) as usize;
+        let mut stream = vec![0u8; frame_bytes];
+        stream.extend(vec![255u8; frame_bytes]);
+        let mut reader = Cursor::new(stream);
+
+        let mut options = ConvertOptions::new(DensityPreset::Low);
+        options.width = width;
+        let mut grids = Vec::new();
+        while let Some(frame) = read_raw_frame(&mut reader, width, height).unwrap() {
+            grids.push(convert_image(&DynamicImage::ImageRgb8(frame), &options).unwrap());
+        }
+
+        assert_eq!(grids.len(), 2);
+        for (grid, expected) in grids.iter().zip([' ', '@']) {
+            assert_eq!(grid.len(), 1);
+            assert_eq!(grid[0].len(), width as usize);
+            assert!(grid[0].iter().all(|pixel| pixel.character == expected));
+        }
+    }
+
+    #[test]
+    fn truncated_raw_frame_is_an_error() {
+        let mut reader = Cursor::new(vec![0u8; 5]);
+        assert!(read_raw_frame(&mut reader, 2, 2).is_err());
+    }
+}
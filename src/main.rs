@@ -1,8 +1,32 @@
-use clap::Parser;
-use colored::*;
-use image::{GenericImageView, Pixel, Rgb, RgbImage};
-use std::fs;
-use std::path::PathBuf;
+use bitify::crop::{Crop, parse_crop};
+use bitify::font::{self, Font, MAX_CELL_SIDE};
+use bitify::graphics::{self, ProtocolChoice};
+use bitify::grid::{self, GridDocument};
+use bitify::metadata::{self, Metadata};
+use bitify::orient::{Flip, Rotation, parse_flip, parse_rotation};
+use bitify::palette::{self, ColorMode, parse_color_mode};
+use bitify::raster::{self, PngCompression, RasterFormat, RasterOptions};
+use bitify::tone::{Adjustments, Luminance, parse_luminance};
+use bitify::{
+    AsciiGrid, html, AsciiPixel, ConvertOptions, DensityPreset, ImageSize, ansi, convert_image, convert_rows, load_image, parse_charset, parse_density, parse_filter,
+    check_brightness, check_contrast, check_gamma, parse_brightness, parse_contrast, parse_gamma, read_source, render_cells, render_ascii_png, save_ascii_image, source_stem, write_ascii_png,
+};
+use clap::{Parser, Subcommand};
+use image::Rgb;
+use image::imageops::FilterType;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+mod config;
+mod live;
+mod play;
+mod serve;
+mod tui;
+mod video;
+
+use config::Config;
 
 #[derive(Parser)]
 #[command(name = "bitify")]
@@ -21,630 +45,1031 @@ EXAMPLES:
   bitify image.jpg                    # Medium density (default)
   bitify -d low image.jpg             # Low density for retro look
   bitify -d ultra -w 120 image.jpg    # Ultra density with custom width
+  bitify --auto-levels --gamma 1.4 dark.jpg   # Brighten a dark photo
+  bitify --invert logo.png            # Dark glyphs on a white background
+  bitify --rotate 90 --flip h scan.jpg        # Fix orientation by hand
+  bitify --crop 25%,10%,50%,50% shot.png      # Convert only a region
+  bitify -d extreme photo.jpg -o art.webp     # Lossy WebP instead of a huge PNG
+  bitify --scale 2 photo.jpg                  # Double-size glyphs for high-DPI screens
+  bitify --png-size 1920x1080 photo.jpg       # Render a wallpaper-sized PNG
+  curl -s URL | bitify - --format png > out.png   # Use in a pipeline
+  bitify --protocol kitty photo.jpg           # Preview the PNG inline (sixel, kitty, iterm)
+  bitify photo.jpg -f json -o grid.json       # Dump the character grid for other renderers
+  bitify grid.json -o grid.png                # Render a dumped grid
+  bitify video clip.mp4 -o clip_ascii.mp4     # Convert a video with ffmpeg
+  bitify play --loop dance.gif                # Play an animation in the terminal
+  bitify live /dev/video0 --fps 15            # Live webcam preview
+  bitify tui photo.jpg                        # Tune settings with a live preview
+  bitify render art.ans --font cozette.bdf    # Render ANSI art to PNG
+  bitify reproduce ~/Bitify/photo_Medium_ascii.png   # Regenerate from embedded settings
+  bitify serve --port 8080                    # Convert uploads over HTTP
 ")]
+#[command(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
 struct Args {
-    image_path: String,
+    #[command(subcommand)]
+    command: Option<Command>,
+    
+    #[arg(required = true)]
+    #[arg(help = "Image to convert, a grid saved with --format json, or - to read from stdin")]
+    image_path: Option<String>,
+    
+    #[command(flatten)]
+    convert: ConvertArgs,
+    
+    #[arg(short, long)]
+    #[arg(value_parser = parse_output_format)]
+    #[arg(help = "Write a single output format instead of printing and saving an image: text, ansi, png, json, html")]
+    format: Option<OutputFormat>,
+
+    #[arg(short, long)]
+    #[arg(help = "Output file for --format, or - for stdout (the default)")]
+    output: Option<String>,
+    
+    #[arg(long, value_name = "FORMAT")]
+    #[arg(value_parser = raster::parse_raster_format)]
+    #[arg(help = "File type of the rendered image: png, jpeg, webp, bmp, tiff, qoi (default: from --output, else png)")]
+    image_format: Option<RasterFormat>,
+    
+    #[arg(long, value_parser = clap::value_parser!(u8).range(1..=100))]
+    #[arg(help = "JPEG or WebP quality, 1-100 (default: 90 for JPEG, 80 for WebP; 100 makes WebP lossless)")]
+    quality: Option<u8>,
+    
+    #[arg(long, default_value = "default")]
+    #[arg(value_parser = raster::parse_png_compression)]
+    #[arg(help = "PNG compression level: fast, default, best")]
+    png_compression: PngCompression,
+    
+    #[arg(long, value_name = "PROTOCOL", conflicts_with_all = ["format", "output"])]
+    #[arg(value_parser = graphics::parse_protocol)]
+    #[arg(help = "Preview the rendered PNG inline instead of printing text: auto, sixel, kitty, iterm")]
+    protocol: Option<ProtocolChoice>,
+    
+    #[arg(long, default_value_t = ansi::DEFAULT_COLOR_TOLERANCE)]
+    #[arg(help = "Largest per-channel difference merged into one color run in terminal output (0 = exact)")]
+    color_tolerance: u8,
+    
+    #[arg(long, global = true)]
+    #[arg(help = "Config file (default: ~/.config/bitify/config.toml)")]
+    config: Option<PathBuf>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    #[command(about = "Convert a video (via ffmpeg) or a directory of frames to ASCII")]
+    Video {
+        #[arg(help = "Video file, or a directory of image frames")]
+        input: String,
+        
+        #[arg(short, long)]
+        #[arg(help = "Output video file, or a directory for a PNG sequence")]
+        output: String,
+        
+        #[arg(long)]
+        #[arg(value_parser = video::parse_frame_rate)]
+        #[arg(help = "Frame rate (default: the source rate, or 24 for frame directories)")]
+        fps: Option<video::FrameRate>,
+        
+        #[command(flatten)]
+        convert: ConvertArgs,
+    },
+    
+    #[command(about = "Play a GIF, a directory of frames or a video in the terminal")]
+    Play {
+        #[arg(help = "GIF, video file, or a directory of image frames")]
+        input: String,
+        
+        #[arg(long)]
+        #[arg(value_parser = video::parse_frame_rate)]
+        #[arg(help = "Frame rate (default: GIF frame delays, the video rate, or 24 for directories)")]
+        fps: Option<video::FrameRate>,
+        
+        #[arg(long = "loop")]
+        #[arg(help = "Start over after the last frame")]
+        looping: bool,
+        
+        #[command(flatten)]
+        convert: ConvertArgs,
+    },
+    
+    #[command(about = "Live ASCII preview from a V4L2 camera (via ffmpeg) or raw frames on stdin")]
+    Live {
+        #[arg(default_value = "/dev/video0")]
+        #[arg(help = "V4L2 capture device")]
+        device: String,
+        
+        #[arg(long, value_name = "WxH")]
+        #[arg(value_parser = parse_size)]
+        #[arg(help = "Read raw rgb24 frames of this size from stdin instead of the device")]
+        raw: Option<(u32, u32)>,
+        
+        #[arg(long, default_value = "15")]
+        #[arg(value_parser = video::parse_frame_rate)]
+        #[arg(help = "Initial preview frame rate (adjust with +/- while running)")]
+        fps: video::FrameRate,
+        
+        #[command(flatten)]
+        convert: ConvertArgs,
+    },
+    
+    #[command(about = "Interactively tune conversion settings with a live preview")]
+    Tui {
+        #[arg(help = "Image to tune")]
+        image_path: String,
+        
+        #[command(flatten)]
+        convert: ConvertArgs,
+    },
+    
+    #[command(about = "Regenerate an ASCII PNG from the settings embedded in it")]
+    Reproduce {
+        #[arg(help = "PNG previously saved by bitify")]
+        png: PathBuf,
+        
+        #[arg(short, long)]
+        #[arg(help = "Where to write the result (default: overwrite the input PNG)")]
+        output: Option<PathBuf>,
+        
+        #[arg(long)]
+        #[arg(help = "Source image to use instead of the recorded path")]
+        source: Option<String>,
+        
+        #[arg(long)]
+        #[arg(help = "Convert even if the source no longer matches the recorded hash")]
+        force: bool,
+    },
     
-    #[arg(short, long, default_value = "80")]
-    #[arg(help = "Output width in characters (overrides density preset)")]
-    width: u32,
+    #[command(about = "Render a plain-text or ANSI (.ans) art file to PNG")]
+    #[command(long_about = "Render a plain-text or ANSI (.ans) art file to PNG.
+
+Foreground colors (including bold as bright), cursor-forward and tabs are
+honored. Background colors are not: every cell is drawn on --background.")]
+    Render {
+        #[arg(help = "Text or ANSI file, or - to read from stdin")]
+        input: String,
+        
+        #[arg(short, long)]
+        #[arg(help = "Output PNG (default: <name>_render.png in the output directory)")]
+        output: Option<PathBuf>,
+        
+        #[arg(long, value_name = "WxH")]
+        #[arg(value_parser = parse_cell_size)]
+        #[arg(help = "Cell size in pixels (default: the font's size, 8x12 for the built-in font)")]
+        cell_size: Option<(u32, u32)>,
+        
+        #[arg(long, default_value = "#000000")]
+        #[arg(value_parser = palette::parse_hex_color)]
+        #[arg(help = "Background color")]
+        background: (u8, u8, u8),
+        
+        #[arg(long)]
+        #[arg(help = "BDF bitmap font to draw glyphs with")]
+        font: Option<PathBuf>,
+        
+        #[arg(long)]
+        #[arg(help = "Wrap lines at this many columns (default: the SAUCE width, or 80 for CP437 art; UTF-8 text is not wrapped)")]
+        columns: Option<usize>,
+    },
     
-    #[arg(short, long, default_value = "medium")]
+    #[command(about = "Serve conversions over HTTP: POST an image, get PNG, text, HTML or JSON back")]
+    Serve {
+        #[arg(long, default_value = "8080")]
+        port: u16,
+        
+        #[arg(long, default_value = "127.0.0.1")]
+        #[arg(help = "Address to listen on (0.0.0.0 for all interfaces)")]
+        bind: String,
+        
+        #[arg(long, default_value = "10", value_name = "MIB")]
+        #[arg(help = "Largest accepted upload in MiB")]
+        max_upload: u64,
+        
+        #[arg(long, default_value = "30", value_name = "SECONDS")]
+        #[arg(help = "Time allowed to receive a request")]
+        timeout: u64,
+        
+        #[arg(long, default_value = "400")]
+        #[arg(help = "Largest output width a request may ask for")]
+        max_width: u32,
+        
+        #[arg(long, default_value = "8")]
+        #[arg(help = "Connections handled at once; more are answered with 503")]
+        max_connections: usize,
+    },
+}
+
+/// Parses the flags recorded by `ConvertOptions::to_args`.
+#[derive(Parser)]
+struct RecordedArgs {
+    #[command(flatten)]
+    convert: ConvertArgs,
+}
+
+/// Rebuilds options from flags recorded by `ConvertOptions::to_args`. The
+/// recorded flags are complete, so the user's config must not leak in.
+fn options_from_args(args: &[String]) -> Result<ConvertOptions, Box<dyn std::error::Error>> {
+    Ok(options_from_flags(args, &Config::default())?)
+}
+
+/// Resolves conversion flags given as separate tokens, as on a command line.
+fn options_from_flags(flags: &[String], config: &Config) -> Result<ConvertOptions, String> {
+    parse_recorded(flags)?.convert.to_options(config)
+}
+
+fn parse_recorded(flags: &[String]) -> Result<RecordedArgs, String> {
+    RecordedArgs::try_parse_from(std::iter::once("bitify").chain(flags.iter().map(String::as_str)))
+        .map_err(|e| e.to_string().lines().next().unwrap_or_default().trim_start_matches("error: ").to_string())
+}
+
+#[derive(Clone, clap::Args)]
+struct ConvertArgs {
+    #[arg(short, long)]
+    #[arg(value_parser = clap::value_parser!(u32).range(1..))]
+    #[arg(help = "Output width in characters (default: the density preset's width)")]
+    width: Option<u32>,
+    
+    #[arg(short, long)]
     #[arg(value_parser = parse_density)]
-    #[arg(help = "ASCII density preset: low, medium, high, ultra, extreme")]
-    density: DensityPreset,
+    #[arg(help = "ASCII density preset: low, medium (default), high, ultra, extreme")]
+    density: Option<DensityPreset>,
+
+    #[arg(short, long)]
+    #[arg(help = "Named preset from the config file (or a built-in density name)")]
+    preset: Option<String>,
+
+    #[arg(long)]
+    #[arg(help = "Custom character ramp from darkest to brightest (overrides density)")]
+    charset: Option<String>,
+
+    #[arg(long)]
+    #[arg(value_parser = parse_filter)]
+    #[arg(help = "Resize filter: nearest (default), triangle, catmullrom, gaussian, lanczos")]
+    filter: Option<FilterType>,
+
+    #[arg(short, long)]
+    #[arg(value_parser = parse_luminance)]
+    #[arg(help = "Brightness model: lstar (perceptual, default), rec709 (linear), rec601 (legacy)")]
+    luminance: Option<Luminance>,
+
+    #[arg(long, allow_negative_numbers = true)]
+    #[arg(value_parser = parse_brightness)]
+    #[arg(help = "Brightness offset applied before mapping (-1.0 to 1.0, default 0)")]
+    brightness: Option<f32>,
+
+    #[arg(long, allow_negative_numbers = true)]
+    #[arg(value_parser = parse_contrast)]
+    #[arg(help = "Contrast multiplier around mid-gray (-5.0 to 5.0, default 1.0 = unchanged; negative inverts)")]
+    contrast: Option<f32>,
+
+    #[arg(long)]
+    #[arg(value_parser = parse_gamma)]
+    #[arg(help = "Gamma correction; values above 1.0 lift shadows (default 1.0)")]
+    gamma: Option<f32>,
+
+    #[arg(long)]
+    #[arg(help = "Stretch the brightness histogram to the full range")]
+    auto_levels: bool,
+
+    #[arg(long, conflicts_with = "auto_levels")]
+    #[arg(help = "Equalize the brightness histogram so the whole ramp is used")]
+    equalize: bool,
+
+    #[arg(short, long)]
+    #[arg(help = "Dark glyphs on a white background, for paper and light pages")]
+    invert: bool,
+
+    #[arg(short, long)]
+    #[arg(value_parser = parse_color_mode)]
+    #[arg(help = "Glyph colors: truecolor (default), 256 (xterm palette), mono")]
+    color: Option<ColorMode>,
+
+    #[arg(long)]
+    #[arg(value_parser = palette::parse_palette)]
+    #[arg(help = "Snap glyph colors to a comma-separated list of hex colors, e.g. #000,#e00,#fff")]
+    palette: Option<palette::Palette>,
+
+    #[arg(long)]
+    #[arg(value_parser = parse_rotation)]
+    #[arg(help = "Rotate clockwise after EXIF orientation: 90, 180, 270")]
+    rotate: Option<Rotation>,
+
+    #[arg(long)]
+    #[arg(value_parser = parse_flip)]
+    #[arg(help = "Mirror the image: h (horizontal) or v (vertical)")]
+    flip: Option<Flip>,
+
+    #[arg(long)]
+    #[arg(help = "Ignore the EXIF orientation tag")]
+    no_exif: bool,
+
+    #[arg(long)]
+    #[arg(value_parser = parse_crop)]
+    #[arg(help = "Crop to x,y,w,h before resizing (pixels or percentages, e.g. 25%,0,50%,100%)")]
+    crop: Option<Crop>,
+
+    #[arg(long)]
+    #[arg(help = "Remove uniform borders before resizing")]
+    trim: bool,
+
+    #[arg(long)]
+    #[arg(help = "Per-channel color difference still treated as border by --trim (default 16)")]
+    trim_tolerance: Option<u8>,
+
+    #[arg(long, value_parser = clap::value_parser!(u32).range(1..=MAX_SCALE as i64))]
+    #[arg(help = "Enlarge each glyph of rendered images by a whole factor (1 = 8x12 pixels)")]
+    scale: Option<u32>,
+
+    #[arg(long, value_name = "WxH", conflicts_with = "scale")]
+    #[arg(value_parser = parse_cell_size)]
+    #[arg(help = "Pixel size of each glyph in rendered images, resampling the font (default 8x12)")]
+    cell_size: Option<(u32, u32)>,
+
+    #[arg(long, value_name = "WxH", conflicts_with_all = ["scale", "cell_size"])]
+    #[arg(value_parser = parse_png_size)]
+    #[arg(help = "Render images at exactly this size, e.g. 1920x1080; -w sets the columns and the source is center-cropped to fit")]
+    png_size: Option<(u32, u32)>,
+
+    #[arg(long, conflicts_with_all = ["scale", "cell_size", "png_size"])]
+    #[arg(help = "Render images at the source image's own pixel size")]
+    match_source: bool,
 }
 
-#[derive(Clone, Debug, PartialEq)]
-enum DensityPreset {
-    Low,
-    Medium, 
-    High,
-    Ultra,
-    Extreme,
+const DEFAULT_TRIM_TOLERANCE: u8 = 16;
+
+fn setting<T>(value: &Option<String>, key: &str, parse: fn(&str) -> Result<T, String>) -> Result<Option<T>, String> {
+    value
+        .as_deref()
+        .map(parse)
+        .transpose()
+        .map_err(|e| format!("{} (setting '{}')", e, key))
+}
+
+fn checked(value: Option<f32>, key: &str, check: fn(f32) -> Result<f32, String>) -> Result<Option<f32>, String> {
+    value.map(check).transpose().map_err(|e| format!("{} (setting '{}')", e, key))
 }
 
-fn parse_density(s: &str) -> Result<DensityPreset, String> {
+impl ConvertArgs {
+    /// These flags layered over `recorded`: whatever was given here wins,
+    /// the rest comes from `recorded`. Mutually exclusive flags (the
+    /// histogram and image size choices) are taken as a group.
+    fn over(&self, recorded: &ConvertArgs) -> ConvertArgs {
+        let histogram = if self.auto_levels || self.equalize { self } else { recorded };
+        let sized = self.scale.is_some() || self.cell_size.is_some() || self.png_size.is_some() || self.match_source;
+        let size = if sized { self } else { recorded };
+        ConvertArgs {
+            width: self.width.or(recorded.width),
+            density: self.density.clone().or_else(|| recorded.density.clone()),
+            preset: self.preset.clone().or_else(|| recorded.preset.clone()),
+            charset: self.charset.clone().or_else(|| recorded.charset.clone()),
+            filter: self.filter.or(recorded.filter),
+            luminance: self.luminance.or(recorded.luminance),
+            brightness: self.brightness.or(recorded.brightness),
+            contrast: self.contrast.or(recorded.contrast),
+            gamma: self.gamma.or(recorded.gamma),
+            auto_levels: histogram.auto_levels,
+            equalize: histogram.equalize,
+            invert: self.invert || recorded.invert,
+            color: self.color.or(recorded.color),
+            palette: self.palette.clone().or_else(|| recorded.palette.clone()),
+            rotate: self.rotate.or(recorded.rotate),
+            flip: self.flip.or(recorded.flip),
+            no_exif: self.no_exif || recorded.no_exif,
+            crop: self.crop.or(recorded.crop),
+            trim: self.trim || recorded.trim,
+            trim_tolerance: self.trim_tolerance.or(recorded.trim_tolerance),
+            scale: size.scale,
+            cell_size: size.cell_size,
+            png_size: size.png_size,
+            match_source: size.match_source,
+        }
+    }
+    
+    /// Options for a grid dumped with the `recorded` flags. Those flags are
+    /// complete, so the config only contributes named presets.
+    fn to_options_over(&self, recorded: &[String], config: &Config) -> Result<ConvertOptions, String> {
+        let presets = Config { presets: config.presets.clone(), ..Config::default() };
+        self.over(&parse_recorded(recorded)?.convert).to_options(&presets)
+    }
+    
+    /// Resolves the final options. Explicit flags win over the selected
+    /// preset, which wins over the config file defaults.
+    fn to_options(&self, config: &Config) -> Result<ConvertOptions, String> {
+        let mut settings = config.defaults.clone();
+        if let Some(name) = &self.preset {
+            match config.preset(name) {
+                Some(preset) => settings = settings.overlay(preset),
+                None => match parse_density(name) {
+                    Ok(density) => settings.density = Some(density.name().to_string()),
+                    Err(_) => return Err(format!("Unknown preset '{}'", name)),
+                },
+            }
+        }
+        
+        let density = match &self.density {
+            Some(density) => density.clone(),
+            None => setting(&settings.density, "density", parse_density)?.unwrap_or(DensityPreset::Medium),
+        };
+        let charset = match &self.charset {
+            Some(charset) => Some(parse_charset(charset)?),
+            None => setting(&settings.ramp, "ramp", parse_charset)?,
+        };
+        let palette = match &self.palette {
+            Some(palette) => Some(palette.clone()),
+            None => settings
+                .palette
+                .as_ref()
+                .map(|entries| palette::parse_palette(&entries.join(",")))
+                .transpose()
+                .map_err(|e| format!("{} (setting 'palette')", e))?,
+        };
+        let defaults = Adjustments::default();
+        
+        Ok(ConvertOptions {
+            width: match self.width.or(settings.width) {
+                Some(0) => return Err("Width must be at least 1 (setting 'width')".to_string()),
+                width => width.unwrap_or_else(|| density.get_default_width()),
+            },
+            density,
+            charset,
+            filter: match self.filter {
+                Some(filter) => filter,
+                None => setting(&settings.filter, "filter", parse_filter)?.unwrap_or(FilterType::Nearest),
+            },
+            luminance: match self.luminance {
+                Some(luminance) => luminance,
+                None => setting(&settings.luminance, "luminance", parse_luminance)?.unwrap_or(Luminance::Lstar),
+            },
+            adjustments: Adjustments {
+                brightness: match self.brightness {
+                    Some(brightness) => brightness,
+                    None => checked(settings.brightness, "brightness", check_brightness)?.unwrap_or(defaults.brightness),
+                },
+                contrast: match self.contrast {
+                    Some(contrast) => contrast,
+                    None => checked(settings.contrast, "contrast", check_contrast)?.unwrap_or(defaults.contrast),
+                },
+                gamma: match self.gamma {
+                    Some(gamma) => gamma,
+                    None => checked(settings.gamma, "gamma", check_gamma)?.unwrap_or(defaults.gamma),
+                },
+                auto_levels: self.auto_levels || (!self.equalize && settings.auto_levels.unwrap_or(false)),
+                equalize: self.equalize || (!self.auto_levels && settings.equalize.unwrap_or(false)),
+            },
+            invert: self.invert || settings.invert.unwrap_or(false),
+            color: match self.color {
+                Some(color) => color,
+                None => setting(&settings.color, "color", parse_color_mode)?.unwrap_or(ColorMode::Truecolor),
+            },
+            palette,
+            exif: !self.no_exif,
+            rotate: self.rotate,
+            flip: self.flip,
+            crop: self.crop,
+            trim: self.trim.then_some(self.trim_tolerance.unwrap_or(DEFAULT_TRIM_TOLERANCE)),
+            cell_size: self.cell_size.or_else(|| {
+                let (width, height) = Font::Builtin.size();
+                self.scale.map(|scale| (width * scale, height * scale))
+            }),
+            image_size: match self.png_size {
+                Some((width, height)) => Some(ImageSize::Pixels(width, height)),
+                None => self.match_source.then_some(ImageSize::Source),
+            },
+        })
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum OutputFormat {
+    Text,
+    Ansi,
+    Png,
+    Json,
+    Html,
+}
+
+fn parse_output_format(s: &str) -> Result<OutputFormat, String> {
     match s.to_lowercase().as_str() {
-        "low" => Ok(DensityPreset::Low),
-        "medium" => Ok(DensityPreset::Medium),
-        "high" => Ok(DensityPreset::High),
-        "ultra" => Ok(DensityPreset::Ultra),
-        "extreme" => Ok(DensityPreset::Extreme),
-        _ => Err(format!("Invalid density '{}'. Use: low, medium, high, ultra, extreme", s)),
+        "text" | "txt" => Ok(OutputFormat::Text),
+        "ansi" | "ans" => Ok(OutputFormat::Ansi),
+        "png" => Ok(OutputFormat::Png),
+        "json" => Ok(OutputFormat::Json),
+        "html" | "htm" => Ok(OutputFormat::Html),
+        _ => Err(format!("Invalid format '{}'. Use: text, ansi, png, json, html", s)),
     }
 }
 
-impl DensityPreset {
-    fn get_chars(&self) -> &'static [char] {
+impl OutputFormat {
+    /// Image extensions other than `.png` also select PNG-style output;
+    /// the file type itself comes from `RasterFormat::from_path`.
+    fn from_path(path: &str) -> Option<OutputFormat> {
+        let extension = Path::new(path).extension()?.to_str()?;
+        parse_output_format(extension)
+            .ok()
+            .or_else(|| raster::parse_raster_format(extension).ok().map(|_| OutputFormat::Png))
+    }
+    
+    fn is_binary(&self) -> bool {
+        matches!(self, OutputFormat::Png)
+    }
+    
+    fn content_type(&self) -> &'static str {
         match self {
-            DensityPreset::Low => &[' ', '.', ':', '+', '#', '@'],
-            DensityPreset::Medium => &[' ', '.', ':', '-', '=', '+', '*', '#', '%', '@'],
-            DensityPreset::High => &[' ', '.', '\'', '`', '^', '"', ',', ':', ';', 'I', 'l', '!', 'i', '>', '<', '~', '+', '_', '-', '?', ']', '[', '}', '{', '1', ')', '(', '|', '\\', '/', 't', 'f', 'j', 'r', 'x', 'n', 'u', 'v', 'c', 'z', 'X', 'Y', 'U', 'J', 'C', 'L', 'Q', '0', 'O', 'Z', 'm', 'w', 'q', 'p', 'd', 'b', 'k', 'h', 'a', 'o', '*', '#', 'M', 'W', '&', '8', '%', 'B', '@'],
-            DensityPreset::Ultra => &[' ', '.', '\'', '`', '^', '"', ',', ':', ';', 'I', 'l', '!', 'i', '>', '<', '~', '+', '_', '-', '?', ']', '[', '}', '{', '1', ')', '(', '|', '\\', '/', 't', 'f', 'j', 'r', 'x', 'n', 'u', 'v', 'c', 'z', 'X', 'Y', 'U', 'J', 'C', 'L', 'Q', '0', 'O', 'Z', 'm', 'w', 'q', 'p', 'd', 'b', 'k', 'h', 'a', 'o', '*', '#', 'M', 'W', '&', '8', '%', 'B', '@', '$'],
-            DensityPreset::Extreme => &[' ', '.', '\'', '`', '^', '"', ',', ':', ';', 'I', 'l', '!', 'i', '>', '<', '~', '+', '_', '-', '?', ']', '[', '}', '{', '1', ')', '(', '|', '\\', '/', 't', 'f', 'j', 'r', 'x', 'n', 'u', 'v', 'c', 'z', 'X', 'Y', 'U', 'J', 'C', 'L', 'Q', '0', 'O', 'Z', 'm', 'w', 'q', 'p', 'd', 'b', 'k', 'h', 'a', 'o', '*', '#', 'M', 'W', '&', '8', '%', 'B', '@', '$', 'A', 'G', 'H', 'K', 'P', 'R', 'S', 'T', 'V', 'g', 's', 'y', 'e', 'F', 'D', 'N', '2', '3', '4', '5', '6', '7', '9', 'E'],
+            OutputFormat::Text | OutputFormat::Ansi => "text/plain; charset=utf-8",
+            OutputFormat::Png => "image/png",
+            OutputFormat::Json => "application/json",
+            OutputFormat::Html => "text/html; charset=utf-8",
         }
     }
     
-    fn get_default_width(&self) -> u32 {
+    /// Plain text is ANSI output without color.
+    fn color_mode(&self, options: &ConvertOptions) -> ColorMode {
         match self {
-            DensityPreset::Low => 40,
-            DensityPreset::Medium => 80,
-            DensityPreset::High => 120,
-            DensityPreset::Ultra => 150,
-            DensityPreset::Extreme => 200,
+            OutputFormat::Text => ColorMode::Mono,
+            _ => options.color,
         }
     }
 }
 
+impl Args {
+    /// `--image-format`, else the output file's extension, else PNG.
+    fn raster_options(&self, destination: Option<&str>) -> RasterOptions {
+        let format = self
+            .image_format
+            .or_else(|| destination.and_then(|destination| RasterFormat::from_path(Path::new(destination))))
+            .unwrap_or_default();
+        RasterOptions { format, quality: self.quality, png_compression: self.png_compression }
+    }
+}
+
+fn parse_size(s: &str) -> Result<(u32, u32), String> {
+    let (width, height) = s
+        .split_once(['x', 'X'])
+        .ok_or_else(|| format!("Invalid size '{}'. Use: WIDTHxHEIGHT, e.g. 640x480", s))?;
+    let width: u32 = width.trim().parse().map_err(|_| format!("Invalid width in '{}'", s))?;
+    let height: u32 = height.trim().parse().map_err(|_| format!("Invalid height in '{}'", s))?;
+    if width == 0 || height == 0 {
+        return Err(format!("Size '{}' must be non-zero", s));
+    }
+    Ok((width, height))
+}
+
+/// Upper bounds that keep rendered images from outgrowing memory.
+const MAX_SCALE: u32 = 16;
+const MAX_IMAGE_SIDE: u32 = 16384;
+
+fn parse_cell_size(s: &str) -> Result<(u32, u32), String> {
+    let (width, height) = parse_size(s)?;
+    if width > MAX_CELL_SIDE || height > MAX_CELL_SIDE {
+        return Err(format!("Cell size '{}' is too large (at most {} pixels a side)", s, MAX_CELL_SIDE));
+    }
+    Ok((width, height))
+}
+
+fn parse_png_size(s: &str) -> Result<(u32, u32), String> {
+    let (width, height) = parse_size(s)?;
+    if width > MAX_IMAGE_SIDE || height > MAX_IMAGE_SIDE {
+        return Err(format!("Image size '{}' is too large (at most {} pixels a side)", s, MAX_IMAGE_SIDE));
+    }
+    Ok((width, height))
+}
+
 fn main() {
     let args = Args::parse();
     
-    let effective_width = if args.width == 80 && args.density != DensityPreset::Medium {
-        args.density.get_default_width()
-    } else {
-        args.width
+    let config = match Config::load(args.config.as_deref()) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            std::process::exit(1);
+        }
+    };
+    
+    if let Some(command) = &args.command {
+        if let Err(e) = run_command(command, &config) {
+            eprintln!("Error: {}", e);
+            std::process::exit(1);
+        }
+        return;
+    }
+    
+    let image_path = args.image_path.as_deref().unwrap_or("-");
+    let options = match args.convert.to_options(&config) {
+        Ok(options) => options,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            std::process::exit(1);
+        }
     };
     
-    match process_image(&args.image_path, effective_width, &args.density) {
-        Ok((ascii_art, ascii_data)) => {
-            println!("{}", ascii_art);
-            if let Err(e) = save_ascii_png(&ascii_data, &args.image_path, &args.density) {
-                eprintln!("Warning: Failed to save ASCII art: {}", e);
-            } else {
-                println!("\n✨ ASCII art saved to ~/Bitify/ (density: {:?})", args.density);
+    if args.format.is_some() || args.output.is_some() {
+        if let Err(e) = write_output(&args, image_path, &options, &config) {
+            eprintln!("Error: {}", e);
+            std::process::exit(1);
+        }
+        return;
+    }
+    
+    match load_input(image_path, options, |recorded| args.convert.to_options_over(recorded, &config)) {
+        Ok((options, ascii_data)) => {
+            if let Err(e) = print_preview(&args, &ascii_data, &options) {
+                eprintln!("Error: {}", e);
+                std::process::exit(1);
+            }
+            let saved = config
+                .output_dir()
+                .map_err(Into::into)
+                .and_then(|dir| save_ascii_image(&ascii_data, image_path, &dir, &options, &args.raster_options(None)));
+            match saved {
+                Ok(path) => println!("\n✨ ASCII art saved to {} (density: {:?})", path.display(), options.density),
+                Err(e) => eprintln!("Warning: Failed to save ASCII art: {}", e),
             }
         }
         Err(e) => eprintln!("Error: {}", e),
     }
 }
 
-#[derive(Clone)]
-struct AsciiPixel {
-    character: char,
-    color: (u8, u8, u8),
+/// Prints the art as terminal text, or as an inline image with `--protocol`.
+fn print_preview(args: &Args, ascii_data: &[Vec<AsciiPixel>], options: &ConvertOptions) -> Result<(), Box<dyn std::error::Error>> {
+    let mut stdout = io::stdout().lock();
+    if let Some(choice) = args.protocol {
+        let protocol = choice
+            .or_else(graphics::detect)
+            .ok_or("Could not detect terminal graphics support; pass --protocol sixel, kitty or iterm")?;
+        stdout.write_all(&graphics::encode(&render_ascii_png(ascii_data, options), protocol)?)?;
+        return Ok(stdout.flush()?);
+    }
+    
+    let mode = if colored::control::SHOULD_COLORIZE.should_colorize() {
+        options.color
+    } else {
+        ColorMode::Mono
+    };
+    ansi::write_rows(&mut stdout, ascii_data, mode, args.color_tolerance)?;
+    writeln!(stdout)?;
+    Ok(())
 }
 
-fn process_image(image_path: &str, target_width: u32, density: &DensityPreset) -> Result<(String, Vec<Vec<AsciiPixel>>), Box<dyn std::error::Error>> {
-    let img = image::open(image_path)?;
-    let (width, height) = img.dimensions();
-    
-    let aspect_ratio = height as f32 / width as f32;
-    let target_height = (target_width as f32 * aspect_ratio * 0.5) as u32;
-    
-    let resized = img.resize_exact(target_width, target_height, image::imageops::FilterType::Nearest);
-    
-    let ascii_chars = density.get_chars();
-    let mut ascii_art = String::new();
-    let mut ascii_data = Vec::new();
-    
-    for y in 0..target_height {
-        let mut row = Vec::new();
-        for x in 0..target_width {
-            let pixel = resized.get_pixel(x, y);
-            let rgba = pixel.to_rgba();
-            
-            let brightness = (rgba[0] as f32 * 0.299 + rgba[1] as f32 * 0.587 + rgba[2] as f32 * 0.114) / 255.0;
-            
-            let char_index = (brightness * (ascii_chars.len() - 1) as f32) as usize;
-            let ascii_char = ascii_chars[char_index];
-            
-            let ascii_pixel = AsciiPixel {
-                character: ascii_char,
-                color: (rgba[0], rgba[1], rgba[2]),
+fn run_command(command: &Command, config: &Config) -> Result<(), Box<dyn std::error::Error>> {
+    match command {
+        Command::Video { input, output, fps, convert } => {
+            video::convert_video(input, output, *fps, &convert.to_options(config)?)
+        }
+        Command::Play { input, fps, looping, convert } => {
+            play::play(input, *fps, *looping, &convert.to_options(config)?)
+        }
+        Command::Live { device, raw, fps, convert } => {
+            let source = match raw {
+                Some((width, height)) => live::LiveSource::RawStdin { width: *width, height: *height },
+                None => live::LiveSource::Device(device.clone()),
             };
-            
-            row.push(ascii_pixel.clone());
-            
-            let colored_char = format!("{}", ascii_char)
-                .truecolor(rgba[0], rgba[1], rgba[2]);
-            
-            ascii_art.push_str(&colored_char.to_string());
+            live::live(&source, *fps, &convert.to_options(config)?)
+        }
+        Command::Tui { image_path, convert } => {
+            tui::run(image_path, &config.output_dir()?, convert.to_options(config)?)
+        }
+        Command::Reproduce { png, output, source, force } => {
+            reproduce(png, output.as_deref().unwrap_or(png), source.as_deref(), *force)
+        }
+        Command::Render { input, output, cell_size, background, font, columns } => {
+            let output = match output {
+                Some(output) => output.clone(),
+                None => config.output_dir()?.join(format!("{}_render.png", source_stem(input))),
+            };
+            render_text_file(input, &output, *cell_size, *background, font.as_deref(), *columns)
+        }
+        Command::Serve { port, bind, max_upload, timeout, max_width, max_connections } => {
+            let limits = serve::Limits {
+                max_upload: max_upload * 1024 * 1024,
+                timeout: Duration::from_secs(*timeout),
+                max_width: *max_width,
+                max_connections: *max_connections,
+            };
+            serve::serve(bind, *port, &limits, config)
         }
-        ascii_data.push(row);
-        ascii_art.push('\n');
     }
-    
-    Ok((ascii_art, ascii_data))
 }
 
-fn save_ascii_png(ascii_data: &[Vec<AsciiPixel>], original_path: &str, density: &DensityPreset) -> Result<(), Box<dyn std::error::Error>> {
-    let home_dir = dirs::home_dir().ok_or("Could not find home directory")?;
-    let bitify_dir = home_dir.join("Bitify");
-    
-    fs::create_dir_all(&bitify_dir)?;
-    
-    let path_buf = PathBuf::from(original_path);
-    let original_name = path_buf
-        .file_stem()
-        .and_then(|s| s.to_str())
-        .unwrap_or("image");
-    
-    let output_path = bitify_dir.join(format!("{}_{:?}_ascii.png", original_name, density));
-    
-    let char_width = 8;
-    let char_height = 12;
-    let img_width = ascii_data[0].len() as u32 * char_width;
-    let img_height = ascii_data.len() as u32 * char_height;
-    
-    let mut img = RgbImage::from_pixel(img_width, img_height, Rgb([0, 0, 0]));
-    
-    for (row_idx, row) in ascii_data.iter().enumerate() {
-        for (col_idx, ascii_pixel) in row.iter().enumerate() {
-            let base_x = col_idx as u32 * char_width;
-            let base_y = row_idx as u32 * char_height;
-            
-            let pattern = get_char_pattern(ascii_pixel.character);
-            let color = Rgb([ascii_pixel.color.0, ascii_pixel.color.1, ascii_pixel.color.2]);
-            
-            for (py, row_pattern) in pattern.iter().enumerate() {
-                for (px, &pixel_on) in row_pattern.iter().enumerate() {
-                    if pixel_on {
-                        let x = base_x + px as u32;
-                        let y = base_y + py as u32;
-                        if x < img_width && y < img_height {
-                            img.put_pixel(x, y, color);
-                        }
-                    }
-                }
-            }
+fn reproduce(png: &Path, output: &Path, source: Option<&str>, force: bool) -> Result<(), Box<dyn std::error::Error>> {
+    let recorded = Metadata::read_from_png(png)?;
+    let source = source.unwrap_or(&recorded.source);
+    if source == "-" {
+        return Err(format!("{} was converted from stdin; pass --source", png.display()).into());
+    }
+    
+    if let Some(expected) = &recorded.source_sha256 {
+        let actual = metadata::sha256_hex(&fs::read(source)?);
+        if actual != *expected && !force {
+            return Err(format!("{} has changed since {} was made; pass --force to convert it anyway", source, png.display()).into());
         }
     }
+    if recorded.version != env!("CARGO_PKG_VERSION") {
+        eprintln!(
+            "Warning: {} was made with bitify {}, this is {}; output may differ",
+            png.display(),
+            recorded.version,
+            env!("CARGO_PKG_VERSION")
+        );
+    }
+    
+    let options = options_from_args(&recorded.args)?;
+    // The recorded flags are complete; a grid's own recording is not needed.
+    let (options, ascii_data) = load_input(source, options.clone(), |_| Ok(options))?;
+    write_ascii_png(&ascii_data, source, output, &options)?;
+    println!("✨ Reproduced {} from {}", output.display(), source);
+    Ok(())
+}
+
+fn render_text_file(input: &str, output: &Path, cell_size: Option<(u32, u32)>, background: (u8, u8, u8), font: Option<&Path>, columns: Option<usize>) -> Result<(), Box<dyn std::error::Error>> {
+    let font = match font {
+        Some(path) => font::load_bdf(path)?,
+        None => Font::Builtin,
+    };
+    // Uncolored text is drawn in whichever of black or white reads on the background.
+    let foreground = if Luminance::Lstar.level(background.0, background.1, background.2) > 0.5 {
+        (0, 0, 0)
+    } else {
+        (255, 255, 255)
+    };
+    
+    let bytes = read_source(input)?;
+    let columns = columns.or_else(|| ansi::art_columns(&bytes));
+    let ascii_data = ansi::parse(&ansi::decode(&bytes), foreground, columns);
+    if ascii_data.first().is_none_or(|row| row.is_empty()) {
+        return Err(format!("{} contains no text", input).into());
+    }
+    
+    let missing = missing_glyphs(&ascii_data, &font);
+    if !missing.is_empty() {
+        let fallback = match font {
+            Font::Builtin => "drawn blank; pass --font with a BDF font that covers them",
+            Font::Bdf(_) => "drawn as '?'",
+        };
+        eprintln!("Warning: the font has no glyph for {} ({})", missing, fallback);
+    }
+    
+    let (cell_width, cell_height) = cell_size.unwrap_or(font.size());
+    let (width, height) = (ascii_data[0].len() as u64 * cell_width as u64, ascii_data.len() as u64 * cell_height as u64);
+    if width > MAX_IMAGE_SIDE as u64 || height > MAX_IMAGE_SIDE as u64 {
+        return Err(format!("The rendered image would be {}x{} pixels, more than {} a side; use a smaller --cell-size or --columns", width, height, MAX_IMAGE_SIDE).into());
+    }
+    
+    let (red, green, blue) = background;
+    let img = render_cells(&ascii_data, &font, (cell_width, cell_height), Rgb([red, green, blue]));
+    if let Some(parent) = output.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    img.save_with_format(output, image::ImageFormat::Png)?;
+    println!("✨ Rendered {} ({}x{} cells)", output.display(), ascii_data[0].len(), ascii_data.len());
+    Ok(())
+}
+
+/// The distinct characters in `ascii_data` that `font` lacks, quoted for a
+/// message; at most a handful are listed.
+fn missing_glyphs(ascii_data: &[Vec<AsciiPixel>], font: &Font) -> String {
+    const LISTED: usize = 8;
+    let mut missing: Vec<char> = ascii_data.iter().flatten().map(|pixel| pixel.character).filter(|&ch| !font.has_glyph(ch)).collect();
+    missing.sort_unstable();
+    missing.dedup();
+    let mut listed: Vec<String> = missing.iter().take(LISTED).map(|ch| format!("'{}'", ch)).collect();
+    if missing.len() > LISTED {
+        listed.push(format!("and {} more", missing.len() - LISTED));
+    }
+    listed.join(", ")
+}
+
+fn write_output(args: &Args, image_path: &str, options: &ConvertOptions, config: &Config) -> Result<(), Box<dyn std::error::Error>> {
+    let destination = args.output.as_deref().unwrap_or("-");
+    let format = match args.format {
+        Some(format) => format,
+        None => OutputFormat::from_path(destination)
+            .ok_or_else(|| format!("Cannot infer the format of '{}'; pass --format", destination))?,
+    };
+    
+    if destination == "-" && format.is_binary() && io::stdout().is_terminal() {
+        return Err("Refusing to write binary output to a terminal; redirect stdout or pass --output".into());
+    }
     
-    img.save(output_path)?;
+    if matches!(format, OutputFormat::Text | OutputFormat::Ansi) && !grid::is_grid_path(image_path) {
+        // Text output streams row by row instead of building the whole grid.
+        let img = load_image(image_path, options)?;
+        let mode = format.color_mode(options);
+        ansi::write_rows(open_output(destination)?, convert_rows(&img, options)?, mode, args.color_tolerance)?;
+        return Ok(());
+    }
+    
+    let (options, ascii_data) = load_input(image_path, options.clone(), |recorded| args.convert.to_options_over(recorded, config))?;
+    let raster = args.raster_options(Some(destination));
+    let bytes = encode_grid(format, ascii_data, image_path, &options, args.color_tolerance, &raster)?;
+    let mut out = open_output(destination)?;
+    out.write_all(&bytes)?;
+    out.flush()?;
     Ok(())
 }
 
-fn get_char_pattern(ch: char) -> &'static [[bool; 8]; 12] {
-    match ch {
-        ' ' => &SPACE,
-        '.' => &DOT,
-        ':' => &COLON,
-        '-' => &DASH,
-        '=' => &EQUALS,
-        '+' => &PLUS,
-        '*' => &ASTERISK,
-        '#' => &HASH,
-        '%' => &PERCENT,
-        '@' => &AT,
-        '\'' => &DOT,
-        '`' => &DOT,
-        '^' => &CARET,
-        '"' => &COLON,
-        ',' => &DOT,
-        ';' => &COLON,
-        'I' => &PIPE_PATTERN,
-        'l' => &PIPE_PATTERN,
-        '!' => &PIPE_PATTERN,
-        'i' => &DOT,
-        '>' => &GREATER,
-        '<' => &LESS,
-        '~' => &TILDE,
-        '_' => &UNDERSCORE,
-        '?' => &QUESTION,
-        ']' => &BRACKET_RIGHT,
-        '[' => &BRACKET_LEFT,
-        '}' => &BRACKET_RIGHT,
-        '{' => &BRACKET_LEFT,
-        '1' => &PIPE_PATTERN,
-        ')' => &PAREN_RIGHT,
-        '(' => &PAREN_LEFT,
-        '|' => &PIPE_PATTERN,
-        '\\' => &BACKSLASH,
-        '/' => &SLASH,
-        't' | 'f' | 'j' | 'r' | 'x' | 'n' | 'u' | 'v' | 'c' | 'z' => &SMALL_BLOCK,
-        'X' | 'Y' | 'U' | 'J' | 'C' | 'L' | 'Q' | 'O' | 'Z' => &MEDIUM_BLOCK,
-        '0' => &ZERO,
-        'm' | 'w' | 'q' | 'p' | 'd' | 'b' | 'k' | 'h' | 'a' | 'o' => &SMALL_BLOCK,
-        'M' | 'W' | 'B' | 'A' | 'G' | 'H' | 'K' | 'P' | 'R' | 'S' | 'T' | 'V' => &LARGE_BLOCK,
-        '&' | '8' | '$' => &LARGE_BLOCK,
-        'g' | 's' | 'y' | 'e' | 'F' | 'D' | 'N' => &MEDIUM_BLOCK,
-        '2' | '3' | '4' | '5' | '6' | '7' | '9' | 'E' => &MEDIUM_BLOCK,
-        _ => &SPACE,
+/// Encodes a finished grid. `source` is recorded in PNG and JSON metadata;
+/// `raster` picks the file type of `OutputFormat::Png`.
+fn encode_grid(format: OutputFormat, ascii_data: AsciiGrid, source: &str, options: &ConvertOptions, tolerance: u8, raster: &RasterOptions) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    Ok(match format {
+        OutputFormat::Text | OutputFormat::Ansi => {
+            let mut bytes = Vec::new();
+            ansi::write_rows(&mut bytes, &ascii_data, format.color_mode(options), tolerance)?;
+            bytes
+        }
+        OutputFormat::Html => html::render_page(&ascii_data, options).into_bytes(),
+        OutputFormat::Png => raster::encode(&render_ascii_png(&ascii_data, options), raster, &Metadata::new(source, options))?,
+        OutputFormat::Json => {
+            let document = GridDocument::new(ascii_data, Metadata::new(source, options));
+            let mut bytes = serde_json::to_vec(&document)?;
+            bytes.push(b'\n');
+            bytes
+        }
+    })
+}
+
+/// Stdout for `-`, otherwise a new file.
+fn open_output(destination: &str) -> io::Result<Box<dyn Write>> {
+    if destination == "-" {
+        Ok(Box::new(io::stdout().lock()))
+    } else {
+        Ok(Box::new(BufWriter::new(File::create(destination)?)))
+    }
+}
+
+/// Converts an image, or loads a grid dumped with `--format json`. A dump
+/// brings back the options it was made with (so e.g. an inverted grid keeps
+/// its white background): `recorded` turns its flags into the options that
+/// are returned alongside.
+fn load_input(
+    image_path: &str,
+    mut options: ConvertOptions,
+    recorded: impl FnOnce(&[String]) -> Result<ConvertOptions, String>,
+) -> Result<(ConvertOptions, AsciiGrid), Box<dyn std::error::Error>> {
+    if grid::is_grid_path(image_path) {
+        let document = GridDocument::read(image_path)?;
+        let options = match &document.settings {
+            Some(settings) => recorded(&settings.args)?,
+            None => options,
+        };
+        if options.image_size == Some(ImageSize::Source) {
+            return Err("--match-source needs a source image, not a grid".into());
+        }
+        return Ok((options, document.cells));
     }
+    let img = load_image(image_path, &options)?;
+    options.resolve_image_size(&img);
+    let ascii_data = convert_image(&img, &options)?;
+    Ok((options, ascii_data))
 }
 
-const SPACE: [[bool; 8]; 12] = [
-    [false; 8],
-    [false; 8],
-    [false; 8],
-    [false; 8],
-    [false; 8],
-    [false; 8],
-    [false; 8],
-    [false; 8],
-    [false; 8],
-    [false; 8],
-    [false; 8],
-    [false; 8],
-];
-
-const DOT: [[bool; 8]; 12] = [
-    [false; 8],
-    [false; 8],
-    [false; 8],
-    [false; 8],
-    [false; 8],
-    [false; 8],
-    [false; 8],
-    [false; 8],
-    [false; 8],
-    [false, false, false, true, true, false, false, false],
-    [false, false, false, true, true, false, false, false],
-    [false; 8],
-];
-
-const COLON: [[bool; 8]; 12] = [
-    [false; 8],
-    [false; 8],
-    [false; 8],
-    [false, false, false, true, true, false, false, false],
-    [false, false, false, true, true, false, false, false],
-    [false; 8],
-    [false; 8],
-    [false, false, false, true, true, false, false, false],
-    [false, false, false, true, true, false, false, false],
-    [false; 8],
-    [false; 8],
-    [false; 8],
-];
-
-const DASH: [[bool; 8]; 12] = [
-    [false; 8],
-    [false; 8],
-    [false; 8],
-    [false; 8],
-    [false; 8],
-    [false; 8],
-    [false, true, true, true, true, true, true, false],
-    [false; 8],
-    [false; 8],
-    [false; 8],
-    [false; 8],
-    [false; 8],
-];
-
-const EQUALS: [[bool; 8]; 12] = [
-    [false; 8],
-    [false; 8],
-    [false; 8],
-    [false; 8],
-    [false, true, true, true, true, true, true, false],
-    [false; 8],
-    [false; 8],
-    [false, true, true, true, true, true, true, false],
-    [false; 8],
-    [false; 8],
-    [false; 8],
-    [false; 8],
-];
-
-const PLUS: [[bool; 8]; 12] = [
-    [false; 8],
-    [false; 8],
-    [false; 8],
-    [false, false, false, true, true, false, false, false],
-    [false, false, false, true, true, false, false, false],
-    [false, true, true, true, true, true, true, false],
-    [false, true, true, true, true, true, true, false],
-    [false, false, false, true, true, false, false, false],
-    [false, false, false, true, true, false, false, false],
-    [false; 8],
-    [false; 8],
-    [false; 8],
-];
-
-const ASTERISK: [[bool; 8]; 12] = [
-    [false; 8],
-    [false; 8],
-    [false, false, true, false, false, true, false, false],
-    [false, false, false, true, true, false, false, false],
-    [false, true, true, true, true, true, true, false],
-    [false, false, false, true, true, false, false, false],
-    [false, false, true, false, false, true, false, false],
-    [false; 8],
-    [false; 8],
-    [false; 8],
-    [false; 8],
-    [false; 8],
-];
-
-const HASH: [[bool; 8]; 12] = [
-    [false; 8],
-    [false, false, true, false, true, false, false, false],
-    [false, false, true, false, true, false, false, false],
-    [false, true, true, true, true, true, true, false],
-    [false, false, true, false, true, false, false, false],
-    [false, false, true, false, true, false, false, false],
-    [false, true, true, true, true, true, true, false],
-    [false, false, true, false, true, false, false, false],
-    [false, false, true, false, true, false, false, false],
-    [false; 8],
-    [false; 8],
-    [false; 8],
-];
-
-const PERCENT: [[bool; 8]; 12] = [
-    [false; 8],
-    [false, true, true, false, false, false, true, false],
-    [false, true, true, false, false, true, false, false],
-    [false, false, false, false, true, false, false, false],
-    [false, false, false, true, false, false, false, false],
-    [false, false, true, false, false, false, false, false],
-    [false, true, false, false, false, false, false, false],
-    [false, true, false, false, true, true, false, false],
-    [false, false, false, false, true, true, false, false],
-    [false; 8],
-    [false; 8],
-    [false; 8],
-];
-
-const AT: [[bool; 8]; 12] = [
-    [false, false, true, true, true, true, false, false],
-    [false, true, false, false, false, false, true, false],
-    [false, true, false, true, true, false, true, false],
-    [false, true, true, false, false, true, true, false],
-    [false, true, true, false, false, true, true, false],
-    [false, true, true, false, false, true, true, false],
-    [false, true, false, true, true, true, false, false],
-    [false, true, false, false, false, false, false, false],
-    [false, false, true, true, true, true, false, false],
-    [false; 8],
-    [false; 8],
-    [false; 8],
-];
-
-const CARET: [[bool; 8]; 12] = [
-    [false; 8],
-    [false, false, false, true, true, false, false, false],
-    [false, false, true, false, false, true, false, false],
-    [false, true, false, false, false, false, true, false],
-    [false; 8],
-    [false; 8],
-    [false; 8],
-    [false; 8],
-    [false; 8],
-    [false; 8],
-    [false; 8],
-    [false; 8],
-];
-
-const GREATER: [[bool; 8]; 12] = [
-    [false; 8],
-    [false; 8],
-    [false, false, true, false, false, false, false, false],
-    [false, false, false, true, false, false, false, false],
-    [false, false, false, false, true, false, false, false],
-    [false, false, false, false, false, true, false, false],
-    [false, false, false, false, true, false, false, false],
-    [false, false, false, true, false, false, false, false],
-    [false, false, true, false, false, false, false, false],
-    [false; 8],
-    [false; 8],
-    [false; 8],
-];
-
-const LESS: [[bool; 8]; 12] = [
-    [false; 8],
-    [false; 8],
-    [false, false, false, false, false, true, false, false],
-    [false, false, false, false, true, false, false, false],
-    [false, false, false, true, false, false, false, false],
-    [false, false, true, false, false, false, false, false],
-    [false, false, false, true, false, false, false, false],
-    [false, false, false, false, true, false, false, false],
-    [false, false, false, false, false, true, false, false],
-    [false; 8],
-    [false; 8],
-    [false; 8],
-];
-
-const TILDE: [[bool; 8]; 12] = [
-    [false; 8],
-    [false; 8],
-    [false; 8],
-    [false; 8],
-    [false, false, true, true, false, false, true, false],
-    [false, true, false, false, true, true, false, false],
-    [false; 8],
-    [false; 8],
-    [false; 8],
-    [false; 8],
-    [false; 8],
-    [false; 8],
-];
-
-const UNDERSCORE: [[bool; 8]; 12] = [
-    [false; 8],
-    [false; 8],
-    [false; 8],
-    [false; 8],
-    [false; 8],
-    [false; 8],
-    [false; 8],
-    [false; 8],
-    [false; 8],
-    [false; 8],
-    [false; 8],
-    [true, true, true, true, true, true, true, true],
-];
-
-const QUESTION: [[bool; 8]; 12] = [
-    [false; 8],
-    [false, false, true, true, true, true, false, false],
-    [false, true, false, false, false, false, true, false],
-    [false, false, false, false, false, true, false, false],
-    [false, false, false, false, true, false, false, false],
-    [false, false, false, true, false, false, false, false],
-    [false, false, false, true, false, false, false, false],
-    [false; 8],
-    [false, false, false, true, false, false, false, false],
-    [false; 8],
-    [false; 8],
-    [false; 8],
-];
-
-const BRACKET_RIGHT: [[bool; 8]; 12] = [
-    [false, true, true, true, false, false, false, false],
-    [false, false, false, true, false, false, false, false],
-    [false, false, false, true, false, false, false, false],
-    [false, false, false, true, false, false, false, false],
-    [false, false, false, true, false, false, false, false],
-    [false, false, false, true, false, false, false, false],
-    [false, false, false, true, false, false, false, false],
-    [false, false, false, true, false, false, false, false],
-    [false, false, false, true, false, false, false, false],
-    [false, true, true, true, false, false, false, false],
-    [false; 8],
-    [false; 8],
-];
-
-const BRACKET_LEFT: [[bool; 8]; 12] = [
-    [false, false, false, true, true, true, false, false],
-    [false, false, false, true, false, false, false, false],
-    [false, false, false, true, false, false, false, false],
-    [false, false, false, true, false, false, false, false],
-    [false, false, false, true, false, false, false, false],
-    [false, false, false, true, false, false, false, false],
-    [false, false, false, true, false, false, false, false],
-    [false, false, false, true, false, false, false, false],
-    [false, false, false, true, false, false, false, false],
-    [false, false, false, true, true, true, false, false],
-    [false; 8],
-    [false; 8],
-];
-
-const PAREN_RIGHT: [[bool; 8]; 12] = [
-    [false, false, true, false, false, false, false, false],
-    [false, false, false, true, false, false, false, false],
-    [false, false, false, false, true, false, false, false],
-    [false, false, false, false, true, false, false, false],
-    [false, false, false, false, true, false, false, false],
-    [false, false, false, false, true, false, false, false],
-    [false, false, false, false, true, false, false, false],
-    [false, false, false, false, true, false, false, false],
-    [false, false, false, true, false, false, false, false],
-    [false, false, true, false, false, false, false, false],
-    [false; 8],
-    [false; 8],
-];
-
-const PAREN_LEFT: [[bool; 8]; 12] = [
-    [false, false, false, false, true, false, false, false],
-    [false, false, false, true, false, false, false, false],
-    [false, false, true, false, false, false, false, false],
-    [false, false, true, false, false, false, false, false],
-    [false, false, true, false, false, false, false, false],
-    [false, false, true, false, false, false, false, false],
-    [false, false, true, false, false, false, false, false],
-    [false, false, true, false, false, false, false, false],
-    [false, false, false, true, false, false, false, false],
-    [false, false, false, false, true, false, false, false],
-    [false; 8],
-    [false; 8],
-];
-
-const PIPE_PATTERN: [[bool; 8]; 12] = [
-    [false, false, false, true, true, false, false, false],
-    [false, false, false, true, true, false, false, false],
-    [false, false, false, true, true, false, false, false],
-    [false, false, false, true, true, false, false, false],
-    [false, false, false, true, true, false, false, false],
-    [false, false, false, true, true, false, false, false],
-    [false, false, false, true, true, false, false, false],
-    [false, false, false, true, true, false, false, false],
-    [false, false, false, true, true, false, false, false],
-    [false, false, false, true, true, false, false, false],
-    [false; 8],
-    [false; 8],
-];
-
-const BACKSLASH: [[bool; 8]; 12] = [
-    [false, true, false, false, false, false, false, false],
-    [false, true, false, false, false, false, false, false],
-    [false, false, true, false, false, false, false, false],
-    [false, false, true, false, false, false, false, false],
-    [false, false, false, true, false, false, false, false],
-    [false, false, false, true, false, false, false, false],
-    [false, false, false, false, true, false, false, false],
-    [false, false, false, false, true, false, false, false],
-    [false, false, false, false, false, true, false, false],
-    [false, false, false, false, false, true, false, false],
-    [false; 8],
-    [false; 8],
-];
-
-const SLASH: [[bool; 8]; 12] = [
-    [false, false, false, false, false, true, false, false],
-    [false, false, false, false, false, true, false, false],
-    [false, false, false, false, true, false, false, false],
-    [false, false, false, false, true, false, false, false],
-    [false, false, false, true, false, false, false, false],
-    [false, false, false, true, false, false, false, false],
-    [false, false, true, false, false, false, false, false],
-    [false, false, true, false, false, false, false, false],
-    [false, true, false, false, false, false, false, false],
-    [false, true, false, false, false, false, false, false],
-    [false; 8],
-    [false; 8],
-];
-
-const ZERO: [[bool; 8]; 12] = [
-    [false, false, true, true, true, true, false, false],
-    [false, true, false, false, false, false, true, false],
-    [false, true, false, false, false, true, true, false],
-    [false, true, false, false, true, false, true, false],
-    [false, true, false, true, false, false, true, false],
-    [false, true, true, false, false, false, true, false],
-    [false, true, false, false, false, false, true, false],
-    [false, false, true, true, true, true, false, false],
-    [false; 8],
-    [false; 8],
-    [false; 8],
-    [false; 8],
-];
-
-const SMALL_BLOCK: [[bool; 8]; 12] = [
-    [false; 8],
-    [false; 8],
-    [false; 8],
-    [false; 8],
-    [false, false, true, true, true, true, false, false],
-    [false, false, true, true, true, true, false, false],
-    [false, false, true, true, true, true, false, false],
-    [false, false, true, true, true, true, false, false],
-    [false; 8],
-    [false; 8],
-    [false; 8],
-    [false; 8],
-];
-
-const MEDIUM_BLOCK: [[bool; 8]; 12] = [
-    [false; 8],
-    [false; 8],
-    [false, true, true, true, true, true, true, false],
-    [false, true, true, true, true, true, true, false],
-    [false, true, true, true, true, true, true, false],
-    [false, true, true, true, true, true, true, false],
-    [false, true, true, true, true, true, true, false],
-    [false, true, true, true, true, true, true, false],
-    [false; 8],
-    [false; 8],
-    [false; 8],
-    [false; 8],
-];
-
-const LARGE_BLOCK: [[bool; 8]; 12] = [
-    [false; 8],
-    [true, true, true, true, true, true, true, true],
-    [true, true, true, true, true, true, true, true],
-    [true, true, true, true, true, true, true, true],
-    [true, true, true, true, true, true, true, true],
-    [true, true, true, true, true, true, true, true],
-    [true, true, true, true, true, true, true, true],
-    [true, true, true, true, true, true, true, true],
-    [true, true, true, true, true, true, true, true],
-    [true, true, true, true, true, true, true, true],
-    [false; 8],
-    [false; 8],
-];
+fn shell_quote(s: &str) -> String {
+    if !s.is_empty() && s.chars().all(|c| c.is_ascii_alphanumeric() || "-_.,/%=".contains(c)) {
+        s.to_string()
+    } else {
+        format!("'{}'", s.replace('\'', "'\\''"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn output_format_follows_the_extension() {
+        assert_eq!(OutputFormat::from_path("art.txt"), Some(OutputFormat::Text));
+        assert_eq!(OutputFormat::from_path("art.ANS"), Some(OutputFormat::Ansi));
+        assert_eq!(OutputFormat::from_path("dir/art.htm"), Some(OutputFormat::Html));
+        assert_eq!(OutputFormat::from_path("grid.json"), Some(OutputFormat::Json));
+        assert_eq!(OutputFormat::from_path("art.jpg"), Some(OutputFormat::Png));
+        assert_eq!(OutputFormat::from_path("art"), None);
+        assert_eq!(OutputFormat::from_path("-"), None);
+        assert!(parse_output_format("gif").is_err());
+    }
+
+    #[test]
+    fn only_image_output_is_binary() {
+        assert!(OutputFormat::Png.is_binary());
+        assert!(!OutputFormat::Ansi.is_binary());
+        let options = ConvertOptions::new(DensityPreset::Medium);
+        assert_eq!(OutputFormat::Text.color_mode(&options), ColorMode::Mono);
+        assert_eq!(OutputFormat::Ansi.color_mode(&options), ColorMode::Truecolor);
+    }
+
+    fn flags(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn library_defaults_match_the_command() {
+        for density in DensityPreset::ALL {
+            let options = options_from_flags(&flags(&["-d", density.name()]), &Config::default()).unwrap();
+            assert_eq!(options, ConvertOptions::new(density.clone()), "{}", density.name());
+        }
+    }
+
+    #[test]
+    fn flags_win_over_presets_and_presets_over_defaults() {
+        let config = Config {
+            defaults: config::Settings { width: Some(100), gamma: Some(1.2), ..Default::default() },
+            presets: [("print".to_string(), config::Settings { width: Some(60), invert: Some(true), ..Default::default() })].into(),
+            ..Config::default()
+        };
+        let options = options_from_flags(&flags(&["-p", "print"]), &config).unwrap();
+        assert_eq!((options.width, options.adjustments.gamma, options.invert), (60, 1.2, true));
+        let options = options_from_flags(&flags(&["-p", "print", "-w", "30"]), &config).unwrap();
+        assert_eq!(options.width, 30);
+        let options = options_from_flags(&[], &config).unwrap();
+        assert_eq!((options.width, options.invert), (100, false));
+        assert!(options_from_flags(&flags(&["-p", "missing"]), &config).is_err());
+    }
+
+    #[test]
+    fn invalid_config_values_name_the_setting() {
+        let config = Config { defaults: config::Settings { density: Some("dense".to_string()), ..Default::default() }, ..Config::default() };
+        let error = options_from_flags(&[], &config).err().unwrap();
+        assert!(error.contains("setting 'density'"), "{}", error);
+    }
+
+    #[test]
+    fn scale_and_cell_size_set_the_glyph_cell() {
+        let cell_size = |args: &[&str]| options_from_flags(&flags(args), &Config::default()).map(|options| options.cell_size);
+        assert_eq!(cell_size(&[]), Ok(None));
+        assert_eq!(cell_size(&["--scale", "3"]), Ok(Some((24, 36))));
+        assert_eq!(cell_size(&["--cell-size", "10x20"]), Ok(Some((10, 20))));
+        assert!(cell_size(&["--scale", "17"]).is_err());
+        assert!(cell_size(&["--scale", "2", "--cell-size", "4x4"]).is_err());
+    }
+
+    #[test]
+    fn sizes_are_parsed_and_capped() {
+        assert_eq!(parse_size("640x480"), Ok((640, 480)));
+        assert_eq!(parse_size("8 X 12"), Ok((8, 12)));
+        assert!(parse_size("640").is_err());
+        assert!(parse_size("0x10").is_err());
+        assert!(parse_size("-1x10").is_err());
+        assert_eq!(parse_cell_size("256x256"), Ok((256, 256)));
+        assert!(parse_cell_size("257x12").is_err());
+    }
+
+    #[test]
+    fn png_size_and_match_source_set_the_image_size() {
+        let image_size = |args: &[&str]| options_from_flags(&flags(args), &Config::default()).map(|options| options.image_size);
+        assert_eq!(image_size(&[]), Ok(None));
+        assert_eq!(image_size(&["--png-size", "1920x1080"]), Ok(Some(ImageSize::Pixels(1920, 1080))));
+        assert_eq!(image_size(&["--match-source"]), Ok(Some(ImageSize::Source)));
+        assert!(image_size(&["--png-size", "16385x10"]).is_err());
+        assert!(image_size(&["--png-size", "64x64", "--scale", "2"]).is_err());
+        assert_eq!(parse_png_size("16384x16384"), Ok((16384, 16384)));
+    }
+
+    #[test]
+    fn zero_width_is_rejected() {
+        assert!(options_from_flags(&flags(&["-w", "0"]), &Config::default()).is_err());
+        let config = Config { defaults: config::Settings { width: Some(0), ..Default::default() }, ..Config::default() };
+        assert!(options_from_flags(&[], &config).err().unwrap().contains("setting 'width'"));
+        assert_eq!(options_from_flags(&flags(&["-w", "5"]), &config).unwrap().width, 5);
+    }
+
+    #[test]
+    fn recorded_args_round_trip() {
+        let mut options = ConvertOptions::new(DensityPreset::High);
+        assert_eq!(options_from_args(&options.to_args()).unwrap(), options);
+
+        options.width = 77;
+        options.charset = Some("-.:#@".chars().collect());
+        options.filter = FilterType::Lanczos3;
+        options.luminance = Luminance::Rec709;
+        options.adjustments = Adjustments { brightness: -0.25, contrast: -0.5, gamma: 1.375, auto_levels: false, equalize: true };
+        options.invert = true;
+        options.color = ColorMode::Ansi256;
+        options.palette = Some(vec![(0, 0, 0), (0xee, 0x10, 0x01)]);
+        options.exif = false;
+        options.rotate = Some(Rotation::Deg270);
+        options.flip = Some(Flip::Vertical);
+        options.crop = Some(parse_crop("10,5%,50%,20").unwrap());
+        options.trim = Some(20);
+        options.cell_size = Some((6, 10));
+        assert_eq!(options_from_args(&options.to_args()).unwrap(), options);
+
+        options.cell_size = None;
+        options.image_size = Some(ImageSize::Pixels(640, 480));
+        assert_eq!(options_from_args(&options.to_args()).unwrap(), options);
+        options.image_size = Some(ImageSize::Source);
+        assert_eq!(options_from_args(&options.to_args()).unwrap(), options);
+    }
+
+    #[test]
+    fn grid_recordings_fill_only_missing_flags() {
+        let recorded = flags(&["--density=high", "--width=20", "--invert", "--cell-size=6x10", "--equalize"]);
+        let given = |args: &[&str]| parse_recorded(&flags(args)).unwrap().convert;
+        let config = Config::default();
+
+        let options = given(&[]).to_options_over(&recorded, &config).unwrap();
+        assert_eq!(options, options_from_args(&recorded).unwrap());
+
+        let options = given(&["--scale", "2", "-w", "5", "--auto-levels"]).to_options_over(&recorded, &config).unwrap();
+        assert_eq!((options.width, options.cell_size, options.invert), (5, Some((16, 24)), true));
+        assert!(options.adjustments.auto_levels && !options.adjustments.equalize);
+
+        let options = given(&["--png-size", "400x300"]).to_options_over(&recorded, &config).unwrap();
+        assert_eq!((options.cell_size, options.image_size), (None, Some(ImageSize::Pixels(400, 300))));
+    }
+}
@@ -0,0 +1,140 @@
+use crate::{ConvertOptions, filter_name};
+#[cfg(feature = "png-render")]
+use crate::raster::PngCompression;
+#[cfg(feature = "png-render")]
+use image::RgbImage;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs;
+#[cfg(feature = "png-render")]
+use std::{fs::File, io::BufReader, path::Path};
+
+#[cfg(feature = "png-render")]
+const SETTINGS_KEYWORD: &str = "bitify:settings";
+
+/// Everything needed to regenerate an ASCII PNG, stored in the PNG itself.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Metadata {
+    pub version: String,
+    pub source: String,
+    pub source_sha256: Option<String>,
+    pub density: String,
+    pub width: u32,
+    pub charset: Option<String>,
+    pub filter: String,
+    pub color: String,
+    /// Command-line flags that reproduce the conversion, without the source.
+    pub args: Vec<String>,
+}
+
+pub fn sha256_hex(bytes: &[u8]) -> String {
+    Sha256::digest(bytes).iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+impl Metadata {
+    /// Describes a conversion of `source`. Files are hashed and recorded by
+    /// absolute path; stdin (`-`) has neither.
+    pub fn new(source: &str, options: &ConvertOptions) -> Metadata {
+        let (source, source_sha256) = if source == "-" {
+            (source.to_string(), None)
+        } else {
+            let absolute = fs::canonicalize(source)
+                .map(|path| path.display().to_string())
+                .unwrap_or_else(|_| source.to_string());
+            (absolute, fs::read(source).ok().map(|bytes| sha256_hex(&bytes)))
+        };
+
+        Metadata {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            source,
+            source_sha256,
+            density: options.density.name().to_string(),
+            width: options.width,
+            charset: options.charset.as_ref().map(|chars| chars.iter().collect()),
+            filter: filter_name(options.filter).to_string(),
+            color: options.color.name().to_string(),
+            args: options.to_args(),
+        }
+    }
+
+    /// Reads the settings embedded by `encode_png`.
+    #[cfg(feature = "png-render")]
+    pub fn read_from_png(path: &Path) -> Result<Metadata, Box<dyn std::error::Error>> {
+        let decoder = png::Decoder::new(BufReader::new(File::open(path)?));
+        let reader = decoder.read_info()?;
+        let info = reader.info();
+
+        let latin1 = info
+            .uncompressed_latin1_text
+            .iter()
+            .find(|chunk| chunk.keyword == SETTINGS_KEYWORD)
+            .map(|chunk| chunk.text.clone());
+        let utf8 = info
+            .utf8_text
+            .iter()
+            .find(|chunk| chunk.keyword == SETTINGS_KEYWORD)
+            .map(|chunk| chunk.get_text())
+            .transpose()?;
+
+        let json = latin1
+            .or(utf8)
+            .ok_or_else(|| format!("{} has no bitify settings", path.display()))?;
+        Ok(serde_json::from_str(&json)?)
+    }
+}
+
+/// Encodes `img` as PNG with the settings JSON in a text chunk. tEXt only
+/// holds Latin-1, so settings with other characters (e.g. a Unicode charset)
+/// go into an iTXt chunk instead.
+#[cfg(feature = "png-render")]
+pub fn encode_png(img: &RgbImage, metadata: &Metadata, compression: PngCompression) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    let mut bytes = Vec::new();
+    let mut encoder = png::Encoder::new(&mut bytes, img.width(), img.height());
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder.set_compression(compression.to_png());
+
+    encoder.add_text_chunk("Software".to_string(), format!("bitify {}", metadata.version))?;
+    let json = serde_json::to_string(metadata)?;
+    if json.chars().all(|c| (c as u32) < 0x100) {
+        encoder.add_text_chunk(SETTINGS_KEYWORD.to_string(), json)?;
+    } else {
+        encoder.add_itxt_chunk(SETTINGS_KEYWORD.to_string(), json)?;
+    }
+
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(img.as_raw())?;
+    writer.finish()?;
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::DensityPreset;
+
+    #[test]
+    fn sources_are_hashed_as_hex() {
+        assert_eq!(sha256_hex(b"abc"), "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad");
+        let metadata = Metadata::new("-", &ConvertOptions::new(DensityPreset::Medium));
+        assert_eq!((metadata.source.as_str(), metadata.source_sha256), ("-", None));
+    }
+
+    /// Latin-1 settings go in tEXt, others in iTXt; both read back.
+    #[cfg(feature = "png-render")]
+    #[test]
+    fn settings_survive_the_png() {
+        let path = std::env::temp_dir().join(format!("bitify-{}-settings.png", std::process::id()));
+        for charset in [" .:-=+*#%@", " ░▒▓█"] {
+            let mut options = ConvertOptions::new(DensityPreset::High);
+            options.charset = Some(charset.chars().collect());
+            let metadata = Metadata::new("-", &options);
+            let png = encode_png(&RgbImage::new(3, 2), &metadata, PngCompression::Fast).unwrap();
+            fs::write(&path, png).unwrap();
+            let read = Metadata::read_from_png(&path).unwrap();
+            assert_eq!(read.charset.as_deref(), Some(charset));
+            assert_eq!(read.args, metadata.args);
+        }
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,150 @@
+use image::DynamicImage;
+#[cfg(feature = "exif")]
+use std::io::Cursor;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Rotation {
+    Deg90,
+    Deg180,
+    Deg270,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Flip {
+    Horizontal,
+    Vertical,
+}
+
+pub fn parse_rotation(s: &str) -> Result<Rotation, String> {
+    match s {
+        "90" => Ok(Rotation::Deg90),
+        "180" => Ok(Rotation::Deg180),
+        "270" => Ok(Rotation::Deg270),
+        _ => Err(format!("Invalid rotation '{}'. Use: 90, 180, 270", s)),
+    }
+}
+
+pub fn parse_flip(s: &str) -> Result<Flip, String> {
+    match s.to_lowercase().as_str() {
+        "h" | "horizontal" => Ok(Flip::Horizontal),
+        "v" | "vertical" => Ok(Flip::Vertical),
+        _ => Err(format!("Invalid flip '{}'. Use: h, v", s)),
+    }
+}
+
+impl Rotation {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Rotation::Deg90 => "90",
+            Rotation::Deg180 => "180",
+            Rotation::Deg270 => "270",
+        }
+    }
+}
+
+impl Flip {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Flip::Horizontal => "h",
+            Flip::Vertical => "v",
+        }
+    }
+}
+
+pub fn rotate(img: DynamicImage, rotation: Rotation) -> DynamicImage {
+    match rotation {
+        Rotation::Deg90 => img.rotate90(),
+        Rotation::Deg180 => img.rotate180(),
+        Rotation::Deg270 => img.rotate270(),
+    }
+}
+
+pub fn flip(img: DynamicImage, flip: Flip) -> DynamicImage {
+    match flip {
+        Flip::Horizontal => img.fliph(),
+        Flip::Vertical => img.flipv(),
+    }
+}
+
+/// Reads the EXIF orientation tag (1-8). Files without EXIF data, or formats
+/// that cannot carry it, report `None`; so does every file without the
+/// `exif` feature.
+#[cfg(feature = "exif")]
+pub fn read_exif_orientation(bytes: &[u8]) -> Option<u32> {
+    let exif = exif::Reader::new()
+        .read_from_container(&mut Cursor::new(bytes))
+        .ok()?;
+    exif.get_field(exif::Tag::Orientation, exif::In::PRIMARY)?
+        .value
+        .get_uint(0)
+}
+
+#[cfg(not(feature = "exif"))]
+pub fn read_exif_orientation(_bytes: &[u8]) -> Option<u32> {
+    None
+}
+
+/// Turns a stored image upright according to its EXIF orientation value.
+pub fn apply_exif_orientation(img: DynamicImage, orientation: u32) -> DynamicImage {
+    match orientation {
+        2 => img.fliph(),
+        3 => img.rotate180(),
+        4 => img.flipv(),
+        5 => img.rotate90().fliph(),
+        6 => img.rotate90(),
+        7 => img.rotate270().fliph(),
+        8 => img.rotate270(),
+        _ => img,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{GenericImageView, Rgb, RgbImage};
+
+    /// A 3x2 image whose pixels all differ: red is x, green is y.
+    fn sample() -> DynamicImage {
+        DynamicImage::ImageRgb8(RgbImage::from_fn(3, 2, |x, y| Rgb([x as u8, y as u8, 0])))
+    }
+
+    /// Asserts that `img` is the sample seen through `source`, which maps an
+    /// output position to the sample position shown there.
+    fn assert_maps(img: &DynamicImage, source: impl Fn(u32, u32) -> (u32, u32)) {
+        assert_eq!(img.dimensions(), (2, 3));
+        for y in 0..3 {
+            for x in 0..2 {
+                let (sx, sy) = source(x, y);
+                assert_eq!(img.get_pixel(x, y).0[..2], [sx as u8, sy as u8], "at {},{}", x, y);
+            }
+        }
+    }
+
+    #[test]
+    fn orientation_5_transposes() {
+        assert_maps(&apply_exif_orientation(sample(), 5), |x, y| (y, x));
+    }
+
+    #[test]
+    fn orientation_7_transverses() {
+        assert_maps(&apply_exif_orientation(sample(), 7), |x, y| (2 - y, 1 - x));
+    }
+
+    #[test]
+    fn orientations_6_and_8_rotate() {
+        assert_maps(&apply_exif_orientation(sample(), 6), |x, y| (y, 1 - x));
+        assert_maps(&apply_exif_orientation(sample(), 8), |x, y| (2 - y, x));
+    }
+
+    #[test]
+    fn unknown_orientations_leave_the_image_alone() {
+        for orientation in [0, 1, 9] {
+            assert_eq!(apply_exif_orientation(sample(), orientation).as_bytes(), sample().as_bytes());
+        }
+    }
+
+    #[test]
+    fn images_without_exif_have_no_orientation() {
+        assert_eq!(read_exif_orientation(b"P6\n1 1\n255\n\0\0\0"), None);
+    }
+}
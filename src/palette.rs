@@ -0,0 +1,182 @@
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ColorMode {
+    Truecolor,
+    Ansi256,
+    Mono,
+}
+
+pub fn parse_color_mode(s: &str) -> Result<ColorMode, String> {
+    match s.to_lowercase().as_str() {
+        "truecolor" | "24bit" | "full" => Ok(ColorMode::Truecolor),
+        "256" | "ansi256" => Ok(ColorMode::Ansi256),
+        "mono" | "none" => Ok(ColorMode::Mono),
+        _ => Err(format!("Invalid color mode '{}'. Use: truecolor, 256, mono", s)),
+    }
+}
+
+impl ColorMode {
+    pub fn name(&self) -> &'static str {
+        match self {
+            ColorMode::Truecolor => "truecolor",
+            ColorMode::Ansi256 => "256",
+            ColorMode::Mono => "mono",
+        }
+    }
+
+    pub fn next(&self) -> ColorMode {
+        match self {
+            ColorMode::Truecolor => ColorMode::Ansi256,
+            ColorMode::Ansi256 => ColorMode::Mono,
+            ColorMode::Mono => ColorMode::Truecolor,
+        }
+    }
+
+    /// Maps a source color to the color a glyph is drawn with. `Mono` draws
+    /// every glyph in the foreground color for the current background.
+    pub fn apply(&self, color: (u8, u8, u8), invert: bool) -> (u8, u8, u8) {
+        match self {
+            ColorMode::Truecolor => color,
+            ColorMode::Ansi256 => ansi256_to_rgb(rgb_to_ansi256(color)),
+            ColorMode::Mono if invert => (0, 0, 0),
+            ColorMode::Mono => (255, 255, 255),
+        }
+    }
+}
+
+const CUBE_LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
+fn nearest_cube_index(channel: u8) -> usize {
+    CUBE_LEVELS
+        .iter()
+        .enumerate()
+        .min_by_key(|&(_, &level)| level.abs_diff(channel))
+        .map(|(i, _)| i)
+        .unwrap_or(0)
+}
+
+fn distance(a: (u8, u8, u8), b: (u8, u8, u8)) -> u32 {
+    let d = |x: u8, y: u8| (x as i32 - y as i32).pow(2) as u32;
+    d(a.0, b.0) + d(a.1, b.1) + d(a.2, b.2)
+}
+
+/// Nearest entry of the xterm 256-color palette, searching the 6x6x6 color
+/// cube and the 24-step gray ramp (the first 16 entries vary by terminal).
+pub fn rgb_to_ansi256(color: (u8, u8, u8)) -> u8 {
+    let (r, g, b) = (
+        nearest_cube_index(color.0),
+        nearest_cube_index(color.1),
+        nearest_cube_index(color.2),
+    );
+    let cube_index = 16 + 36 * r + 6 * g + b;
+    let cube_color = (CUBE_LEVELS[r], CUBE_LEVELS[g], CUBE_LEVELS[b]);
+
+    let average = (color.0 as u32 + color.1 as u32 + color.2 as u32) / 3;
+    let gray_step = (average.saturating_sub(3) / 10).min(23);
+    let gray_level = (8 + gray_step * 10) as u8;
+    let gray_index = 232 + gray_step as usize;
+
+    if distance(color, (gray_level, gray_level, gray_level)) < distance(color, cube_color) {
+        gray_index as u8
+    } else {
+        cube_index as u8
+    }
+}
+
+pub fn ansi256_to_rgb(index: u8) -> (u8, u8, u8) {
+    match index {
+        16..=231 => {
+            let i = (index - 16) as usize;
+            (CUBE_LEVELS[i / 36], CUBE_LEVELS[(i / 6) % 6], CUBE_LEVELS[i % 6])
+        }
+        232..=255 => {
+            let level = 8 + (index - 232) * 10;
+            (level, level, level)
+        }
+        _ => {
+            const BASIC: [(u8, u8, u8); 16] = [
+                (0, 0, 0), (128, 0, 0), (0, 128, 0), (128, 128, 0),
+                (0, 0, 128), (128, 0, 128), (0, 128, 128), (192, 192, 192),
+                (128, 128, 128), (255, 0, 0), (0, 255, 0), (255, 255, 0),
+                (0, 0, 255), (255, 0, 255), (0, 255, 255), (255, 255, 255),
+            ];
+            BASIC[index as usize]
+        }
+    }
+}
+
+pub fn parse_hex_color(s: &str) -> Result<(u8, u8, u8), String> {
+    let hex = s.trim().trim_start_matches('#');
+    let expanded: String = match hex.len() {
+        3 => hex.chars().flat_map(|c| [c, c]).collect(),
+        6 => hex.to_string(),
+        _ => return Err(format!("Invalid color '{}'. Use #rgb or #rrggbb", s)),
+    };
+    let channel = |i: usize| {
+        u8::from_str_radix(&expanded[i..i + 2], 16).map_err(|_| format!("Invalid color '{}'. Use #rgb or #rrggbb", s))
+    };
+    Ok((channel(0)?, channel(2)?, channel(4)?))
+}
+
+/// A named type so clap treats `--palette` as one value rather than a list.
+pub type Palette = Vec<(u8, u8, u8)>;
+
+/// Parses a comma-separated list of hex colors, e.g. `#000,#e00,#0e0,#fff`.
+pub fn parse_palette(s: &str) -> Result<Palette, String> {
+    let colors = s
+        .split(',')
+        .filter(|entry| !entry.trim().is_empty())
+        .map(parse_hex_color)
+        .collect::<Result<Vec<_>, _>>()?;
+    if colors.is_empty() {
+        return Err("Palette needs at least one color".to_string());
+    }
+    Ok(colors)
+}
+
+pub fn format_hex_color(color: (u8, u8, u8)) -> String {
+    format!("#{:02x}{:02x}{:02x}", color.0, color.1, color.2)
+}
+
+pub fn nearest_in_palette(palette: &[(u8, u8, u8)], color: (u8, u8, u8)) -> (u8, u8, u8) {
+    palette
+        .iter()
+        .copied()
+        .min_by_key(|&entry| distance(entry, color))
+        .unwrap_or(color)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ansi256_round_trips_cube_and_gray_entries() {
+        for index in 16..=255 {
+            assert_eq!(rgb_to_ansi256(ansi256_to_rgb(index)), index, "index {}", index);
+        }
+        assert_eq!(rgb_to_ansi256((255, 0, 0)), 196);
+        assert_eq!(rgb_to_ansi256((128, 128, 128)), 244);
+    }
+
+    #[test]
+    fn color_modes_map_glyph_colors() {
+        assert_eq!(ColorMode::Truecolor.apply((1, 2, 3), false), (1, 2, 3));
+        assert_eq!(ColorMode::Ansi256.apply((250, 10, 10), false), (255, 0, 0));
+        assert_eq!(ColorMode::Mono.apply((1, 2, 3), false), (255, 255, 255));
+        assert_eq!(ColorMode::Mono.apply((1, 2, 3), true), (0, 0, 0));
+        for mode in [ColorMode::Truecolor, ColorMode::Ansi256, ColorMode::Mono] {
+            assert_eq!(parse_color_mode(mode.name()), Ok(mode));
+        }
+    }
+
+    #[test]
+    fn palettes_parse_and_snap() {
+        let palette = parse_palette("#000, #e00,,#ffffff").unwrap();
+        assert_eq!(palette, [(0, 0, 0), (0xee, 0, 0), (255, 255, 255)]);
+        assert_eq!(nearest_in_palette(&palette, (200, 30, 30)), (0xee, 0, 0));
+        assert_eq!(format_hex_color((0xee, 0, 0)), "#ee0000");
+        for bad in ["", ",", "#12", "#gggggg"] {
+            assert!(parse_palette(bad).is_err(), "{}", bad);
+        }
+    }
+}
@@ -0,0 +1,368 @@
+use crate::video::{FrameRate, FrameSource, probe_frame_rate};
+use bitify::palette::{ColorMode, rgb_to_ansi256};
+use bitify::{AsciiGrid, ConvertOptions, convert_image, transform_image};
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use crossterm::style::{Color, Print, ResetColor, SetForegroundColor};
+use crossterm::{cursor, execute, queue, terminal};
+use image::AnimationDecoder;
+use image::codecs::gif::GifDecoder;
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{self, BufReader, IsTerminal, Write};
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+const SEEK_STEP: usize = 10;
+/// Converted frames kept for seeking back; older ones are dropped so long
+/// videos play in bounded memory.
+const CACHED_FRAMES: usize = 600;
+// Browsers treat GIF delays this short as "unspecified" and fall back to 100ms.
+const MIN_GIF_DELAY: Duration = Duration::from_millis(20);
+const DEFAULT_GIF_DELAY: Duration = Duration::from_millis(100);
+
+enum Frames {
+    Gif(std::vec::IntoIter<image::Frame>),
+    Stream { source: FrameSource, delay: Duration },
+}
+
+impl Frames {
+    fn open(input: &str, fps: Option<FrameRate>) -> Result<Frames, Box<dyn std::error::Error>> {
+        let path = Path::new(input);
+        let is_gif = path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("gif"));
+
+        if is_gif && fps.is_none() {
+            let decoder = GifDecoder::new(BufReader::new(File::open(path)?))?;
+            let frames = decoder.into_frames().collect_frames()?;
+            return Ok(Frames::Gif(frames.into_iter()));
+        }
+
+        let rate = match fps {
+            Some(rate) => rate,
+            None if path.is_dir() => FrameRate { num: 24, den: 1 },
+            None => probe_frame_rate(input)?,
+        };
+        Ok(Frames::Stream {
+            source: FrameSource::open(input)?,
+            delay: Duration::from_secs_f64(1.0 / rate.as_f64()),
+        })
+    }
+
+    fn next_frame(&mut self) -> Result<Option<(image::DynamicImage, Duration)>, Box<dyn std::error::Error>> {
+        match self {
+            Frames::Gif(frames) => Ok(frames.next().map(|frame| {
+                let mut delay = Duration::from(frame.delay());
+                if delay < MIN_GIF_DELAY {
+                    delay = DEFAULT_GIF_DELAY;
+                }
+                (image::DynamicImage::ImageRgba8(frame.into_buffer()), delay)
+            })),
+            Frames::Stream { source, delay } => Ok(source.next_frame()?.map(|img| (img, *delay))),
+        }
+    }
+}
+
+/// Keeps the terminal in raw mode on the alternate screen and restores it
+/// when dropped, including on early returns and panics.
+pub struct TerminalGuard;
+
+impl TerminalGuard {
+    pub fn enter() -> io::Result<TerminalGuard> {
+        terminal::enable_raw_mode()?;
+        execute!(io::stdout(), terminal::EnterAlternateScreen, cursor::Hide, terminal::Clear(terminal::ClearType::All))?;
+        Ok(TerminalGuard)
+    }
+}
+
+impl Drop for TerminalGuard {
+    fn drop(&mut self) {
+        let _ = execute!(io::stdout(), ResetColor, cursor::Show, terminal::LeaveAlternateScreen);
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+enum Action {
+    Quit,
+    TogglePause,
+    Seek(isize),
+}
+
+fn read_action(timeout: Duration) -> io::Result<Option<Action>> {
+    if !event::poll(timeout)? {
+        return Ok(None);
+    }
+    let Event::Key(KeyEvent { code, modifiers, kind, .. }) = event::read()? else {
+        return Ok(None);
+    };
+    if kind == KeyEventKind::Release {
+        return Ok(None);
+    }
+    let action = match code {
+        KeyCode::Char('c') if modifiers.contains(KeyModifiers::CONTROL) => Action::Quit,
+        KeyCode::Char('q') | KeyCode::Esc => Action::Quit,
+        KeyCode::Char(' ') | KeyCode::Char('p') => Action::TogglePause,
+        KeyCode::Right | KeyCode::Char('l') => Action::Seek(SEEK_STEP as isize),
+        KeyCode::Left | KeyCode::Char('h') => Action::Seek(-(SEEK_STEP as isize)),
+        KeyCode::Char('.') => Action::Seek(1),
+        KeyCode::Char(',') => Action::Seek(-1),
+        _ => return Ok(None),
+    };
+    Ok(Some(action))
+}
+
+/// Writes only the cells that differ from `previous`, moving the cursor and
+/// changing color only when the next changed cell needs it. Rows past
+/// `max_rows` are clipped so the status line stays on screen. `mode` picks
+/// the escapes, as in `ansi::AnsiWriter`.
+pub fn draw_frame(out: &mut impl Write, grid: &AsciiGrid, previous: Option<&AsciiGrid>, max_rows: usize, mode: ColorMode) -> io::Result<()> {
+    let same_shape = previous.is_some_and(|prev| {
+        prev.len() == grid.len() && prev.iter().zip(grid).all(|(a, b)| a.len() == b.len())
+    });
+    if !same_shape {
+        queue!(out, terminal::Clear(terminal::ClearType::All))?;
+    }
+
+    let mut cursor_at: Option<(usize, usize)> = None;
+    let mut current_color = None;
+    for (y, row) in grid.iter().enumerate().take(max_rows) {
+        for (x, pixel) in row.iter().enumerate() {
+            if same_shape && let Some(prev) = previous {
+                let old = &prev[y][x];
+                if old.character == pixel.character && old.color == pixel.color {
+                    continue;
+                }
+            }
+            if cursor_at != Some((x, y)) {
+                queue!(out, cursor::MoveTo(x as u16, y as u16))?;
+            }
+            if current_color != Some(pixel.color) {
+                let (r, g, b) = pixel.color;
+                let color = match mode {
+                    ColorMode::Truecolor => Color::Rgb { r, g, b },
+                    ColorMode::Ansi256 => Color::AnsiValue(rgb_to_ansi256(pixel.color)),
+                    ColorMode::Mono => Color::Reset,
+                };
+                queue!(out, SetForegroundColor(color))?;
+                current_color = Some(pixel.color);
+            }
+            queue!(out, Print(pixel.character))?;
+            cursor_at = Some((x + 1, y));
+        }
+    }
+    Ok(())
+}
+
+fn draw_status(out: &mut impl Write, row: usize, index: usize, decoded: usize, finished: bool, paused: bool) -> io::Result<()> {
+    let total = if finished { decoded.to_string() } else { format!("{}+", decoded) };
+    let state = if paused { "paused" } else { "playing" };
+    queue!(
+        out,
+        cursor::MoveTo(0, row as u16),
+        ResetColor,
+        terminal::Clear(terminal::ClearType::CurrentLine),
+        Print(format!("frame {}/{} [{}]  space: pause  ←/→: seek  ,/.: step  q: quit", index + 1, total, state)),
+    )
+}
+
+/// Converted frames with their delays: a window of the most recent
+/// `CACHED_FRAMES`, addressed by frame number from the start of the input.
+struct FrameCache {
+    frames: Frames,
+    grids: VecDeque<(AsciiGrid, Duration)>,
+    /// Frame number of `grids[0]`.
+    first: usize,
+    finished: bool,
+}
+
+impl FrameCache {
+    fn new(frames: Frames) -> FrameCache {
+        FrameCache { frames, grids: VecDeque::new(), first: 0, finished: false }
+    }
+
+    /// Frames converted so far, including dropped ones.
+    fn decoded(&self) -> usize {
+        self.first + self.grids.len()
+    }
+
+    fn get(&self, index: usize) -> Option<&(AsciiGrid, Duration)> {
+        self.grids.get(index.checked_sub(self.first)?)
+    }
+
+    fn decode_next(&mut self, options: &ConvertOptions) -> Result<(), Box<dyn std::error::Error>> {
+        if self.finished {
+            return Ok(());
+        }
+        match self.frames.next_frame()? {
+            Some((img, delay)) => {
+                let img = transform_image(img, options)?;
+                self.grids.push_back((convert_image(&img, options)?, delay));
+                if self.grids.len() > CACHED_FRAMES {
+                    self.grids.pop_front();
+                    self.first += 1;
+                }
+            }
+            None => self.finished = true,
+        }
+        Ok(())
+    }
+}
+
+/// Plays a GIF, a directory of frames or a video in the terminal. Recent
+/// converted frames are cached so that seeking backwards does not re-decode
+/// the source; looping reopens it once the first frames have been dropped.
+pub fn play(input: &str, fps: Option<FrameRate>, looping: bool, options: &ConvertOptions) -> Result<(), Box<dyn std::error::Error>> {
+    if !io::stdout().is_terminal() {
+        return Err("play needs an interactive terminal; use the video command to write files".into());
+    }
+    let mut cache = FrameCache::new(Frames::open(input, fps)?);
+
+    let mut options = options.clone();
+    let (columns, rows) = terminal::size()?;
+    options.width = options.width.min(columns as u32);
+    let max_rows = (rows as usize).saturating_sub(1);
+
+    cache.decode_next(&options)?;
+    let Some((first_grid, _)) = cache.get(0) else {
+        return Err(format!("No frames decoded from '{}'", input).into());
+    };
+    let status_row = first_grid.len().min(max_rows);
+
+    let _guard = TerminalGuard::enter()?;
+    let mut stdout = io::stdout().lock();
+
+    let mut index = 0;
+    // Cleared when the cache is rebuilt, so the next frame is drawn in full.
+    let mut shown: Option<usize> = None;
+    let mut paused = false;
+    let mut deadline = Instant::now();
+
+    loop {
+        if shown != Some(index) {
+            let (grid, delay) = cache.get(index).ok_or("frame missing from cache")?;
+            let previous = shown.and_then(|shown| cache.get(shown)).map(|(grid, _)| grid);
+            draw_frame(&mut stdout, grid, previous, max_rows, options.color)?;
+            draw_status(&mut stdout, status_row, index, cache.decoded(), cache.finished, paused)?;
+            stdout.flush()?;
+            shown = Some(index);
+            deadline += *delay;
+        }
+
+        let timeout = if paused {
+            Duration::from_millis(250)
+        } else {
+            deadline.saturating_duration_since(Instant::now())
+        };
+
+        match read_action(timeout)? {
+            Some(Action::Quit) => break,
+            Some(Action::TogglePause) => {
+                paused = !paused;
+                deadline = Instant::now();
+                draw_status(&mut stdout, status_row, index, cache.decoded(), cache.finished, paused)?;
+                stdout.flush()?;
+            }
+            Some(Action::Seek(step)) => {
+                let target = index.saturating_add_signed(step).max(cache.first);
+                while cache.decoded() <= target && !cache.finished {
+                    cache.decode_next(&options)?;
+                }
+                index = target.min(cache.decoded() - 1);
+                deadline = Instant::now();
+            }
+            None if !paused && Instant::now() >= deadline => {
+                if index + 1 >= cache.decoded() {
+                    cache.decode_next(&options)?;
+                }
+                if index + 1 < cache.decoded() {
+                    index += 1;
+                } else if looping {
+                    if cache.first > 0 {
+                        cache = FrameCache::new(Frames::open(input, fps)?);
+                        cache.decode_next(&options)?;
+                        shown = None;
+                    }
+                    index = 0;
+                } else {
+                    paused = true;
+                    draw_status(&mut stdout, status_row, index, cache.decoded(), cache.finished, paused)?;
+                    stdout.flush()?;
+                }
+                if shown == Some(index) && let Some((_, delay)) = cache.get(index) {
+                    deadline = Instant::now() + *delay;
+                }
+            }
+            None => {}
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bitify::{AsciiPixel, DensityPreset};
+
+    fn grid(text: &[&str], color: (u8, u8, u8)) -> AsciiGrid {
+        text.iter()
+            .map(|row| row.chars().map(|character| AsciiPixel { character, color }).collect())
+            .collect()
+    }
+
+    fn draw(grid: &AsciiGrid, previous: Option<&AsciiGrid>, max_rows: usize, mode: ColorMode) -> String {
+        let mut out = Vec::new();
+        draw_frame(&mut out, grid, previous, max_rows, mode).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn unchanged_frames_draw_nothing() {
+        let frame = grid(&["ab", "cd"], (1, 2, 3));
+        assert_eq!(draw(&frame, Some(&frame), 10, ColorMode::Truecolor), "");
+    }
+
+    #[test]
+    fn only_changed_cells_are_redrawn() {
+        let before = grid(&["ab", "cd"], (1, 2, 3));
+        let mut after = before.clone();
+        after[1][1].character = 'X';
+        // Move to column 2, row 2; then the color and the glyph.
+        assert_eq!(draw(&after, Some(&before), 10, ColorMode::Truecolor), "\x1b[2;2H\x1b[38;2;1;2;3mX");
+    }
+
+    #[test]
+    fn reshaped_frames_clear_the_screen_and_clip_rows() {
+        let frame = grid(&["ab", "cd", "ef"], (1, 2, 3));
+        let output = draw(&frame, Some(&grid(&["ab"], (1, 2, 3))), 2, ColorMode::Truecolor);
+        assert!(output.starts_with("\x1b[2J"));
+        assert!(output.contains("cd") && !output.contains("ef"));
+    }
+
+    #[test]
+    fn color_modes_pick_the_escapes() {
+        let frame = grid(&["a"], (255, 0, 0));
+        assert!(draw(&frame, None, 10, ColorMode::Ansi256).contains("\x1b[38;5;196m"));
+        assert!(!draw(&frame, None, 10, ColorMode::Mono).contains("38;"));
+    }
+
+    #[test]
+    fn the_cache_keeps_only_recent_frames() {
+        let frames: Vec<image::Frame> = (0..CACHED_FRAMES + 10)
+            .map(|_| image::Frame::new(image::RgbaImage::new(2, 2)))
+            .collect();
+        let mut cache = FrameCache::new(Frames::Gif(frames.into_iter()));
+        let mut options = ConvertOptions::new(DensityPreset::Low);
+        options.width = 2;
+        while !cache.finished {
+            cache.decode_next(&options).unwrap();
+        }
+
+        assert_eq!(cache.decoded(), CACHED_FRAMES + 10);
+        assert_eq!(cache.grids.len(), CACHED_FRAMES);
+        assert!(cache.get(9).is_none());
+        assert!(cache.get(10).is_some());
+        assert!(cache.get(CACHED_FRAMES + 10).is_none());
+    }
+}
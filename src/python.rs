@@ -0,0 +1,79 @@
+//! Python module, built with `maturin develop --release`:
+//!
+//! ```python
+//! import bitify
+//! png = bitify.to_png(open("photo.jpg", "rb").read(), density="high")
+//! pixels = bitify.to_array(data, width=120)   # (height, width, 3) uint8
+//! ```
+
+use crate::ansi::{self, DEFAULT_COLOR_TOLERANCE};
+use crate::palette::{ColorMode, parse_color_mode};
+use crate::{AsciiGrid, ConvertOptions, convert_bytes, encode_ascii_png, parse_charset, parse_density, render_ascii_png};
+use numpy::{PyArray1, PyArray3, PyArrayMethods};
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::types::PyBytes;
+
+fn options(density: &str, width: Option<u32>, charset: Option<&str>, color: &str, invert: bool) -> PyResult<ConvertOptions> {
+    let mut options = ConvertOptions::new(parse_density(density).map_err(PyValueError::new_err)?);
+    if let Some(width) = width {
+        if width == 0 {
+            return Err(PyValueError::new_err("width must be at least 1"));
+        }
+        options.width = width;
+    }
+    if let Some(charset) = charset {
+        options.charset = Some(parse_charset(charset).map_err(PyValueError::new_err)?);
+    }
+    options.color = parse_color_mode(color).map_err(PyValueError::new_err)?;
+    options.invert = invert;
+    Ok(options)
+}
+
+/// Converts without holding the GIL so other Python threads keep running.
+fn convert(py: Python<'_>, data: &[u8], options: &ConvertOptions) -> PyResult<AsciiGrid> {
+    py.detach(|| convert_bytes(data, options).map_err(|e| e.to_string()))
+        .map_err(PyValueError::new_err)
+}
+
+/// The ASCII art PNG, with settings embedded as the command writes them.
+#[pyfunction]
+#[pyo3(signature = (data, density = "medium", width = None, charset = None, color = "truecolor", invert = false))]
+fn to_png<'py>(py: Python<'py>, data: &[u8], density: &str, width: Option<u32>, charset: Option<&str>, color: &str, invert: bool) -> PyResult<Bound<'py, PyBytes>> {
+    let options = options(density, width, charset, color, invert)?;
+    let ascii_data = convert(py, data, &options)?;
+    let png = encode_ascii_png(&ascii_data, "-", &options).map_err(|e| PyValueError::new_err(e.to_string()))?;
+    Ok(PyBytes::new(py, &png))
+}
+
+/// The rendered image as a `(height, width, 3)` uint8 array.
+#[pyfunction]
+#[pyo3(signature = (data, density = "medium", width = None, charset = None, color = "truecolor", invert = false))]
+fn to_array<'py>(py: Python<'py>, data: &[u8], density: &str, width: Option<u32>, charset: Option<&str>, color: &str, invert: bool) -> PyResult<Bound<'py, PyArray3<u8>>> {
+    let options = options(density, width, charset, color, invert)?;
+    let ascii_data = convert(py, data, &options)?;
+    let img = render_ascii_png(&ascii_data, &options);
+    let shape = [img.height() as usize, img.width() as usize, 3];
+    PyArray1::from_vec(py, img.into_raw()).reshape(shape)
+}
+
+/// The art as text; `ansi=True` adds terminal color escapes.
+#[pyfunction]
+#[pyo3(signature = (data, density = "medium", width = None, charset = None, color = "truecolor", invert = false, ansi = false))]
+#[allow(clippy::too_many_arguments)]
+fn to_text(py: Python<'_>, data: &[u8], density: &str, width: Option<u32>, charset: Option<&str>, color: &str, invert: bool, ansi: bool) -> PyResult<String> {
+    let options = options(density, width, charset, color, invert)?;
+    let ascii_data = convert(py, data, &options)?;
+    let mode = if ansi { options.color } else { ColorMode::Mono };
+    let mut text = Vec::new();
+    ansi::write_rows(&mut text, &ascii_data, mode, DEFAULT_COLOR_TOLERANCE).map_err(|e| PyValueError::new_err(e.to_string()))?;
+    Ok(String::from_utf8_lossy(&text).into_owned())
+}
+
+#[pymodule(name = "bitify")]
+fn python_module(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_function(wrap_pyfunction!(to_png, m)?)?;
+    m.add_function(wrap_pyfunction!(to_array, m)?)?;
+    m.add_function(wrap_pyfunction!(to_text, m)?)?;
+    Ok(())
+}
@@ -0,0 +1,211 @@
+//! File types for rendered images. Only PNG carries the embedded settings
+//! that `bitify reproduce` reads.
+
+use crate::metadata::{self, Metadata};
+use image::{DynamicImage, ImageFormat, RgbImage};
+use std::io::Cursor;
+use std::path::Path;
+
+pub const DEFAULT_JPEG_QUALITY: u8 = 90;
+pub const DEFAULT_WEBP_QUALITY: u8 = 80;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum RasterFormat {
+    #[default]
+    Png,
+    Jpeg,
+    Webp,
+    Bmp,
+    Tiff,
+    Qoi,
+}
+
+pub fn parse_raster_format(s: &str) -> Result<RasterFormat, String> {
+    match s.to_lowercase().as_str() {
+        "png" => Ok(RasterFormat::Png),
+        "jpeg" | "jpg" => Ok(RasterFormat::Jpeg),
+        "webp" => Ok(RasterFormat::Webp),
+        "bmp" => Ok(RasterFormat::Bmp),
+        "tiff" | "tif" => Ok(RasterFormat::Tiff),
+        "qoi" => Ok(RasterFormat::Qoi),
+        _ => Err(format!("Invalid image format '{}'. Use: png, jpeg, webp, bmp, tiff, qoi", s)),
+    }
+}
+
+impl RasterFormat {
+    pub fn from_path(path: &Path) -> Option<RasterFormat> {
+        parse_raster_format(path.extension()?.to_str()?).ok()
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            RasterFormat::Png => "png",
+            RasterFormat::Jpeg => "jpg",
+            RasterFormat::Webp => "webp",
+            RasterFormat::Bmp => "bmp",
+            RasterFormat::Tiff => "tiff",
+            RasterFormat::Qoi => "qoi",
+        }
+    }
+
+    pub fn mime_type(&self) -> &'static str {
+        match self {
+            RasterFormat::Png => "image/png",
+            RasterFormat::Jpeg => "image/jpeg",
+            RasterFormat::Webp => "image/webp",
+            RasterFormat::Bmp => "image/bmp",
+            RasterFormat::Tiff => "image/tiff",
+            RasterFormat::Qoi => "image/x-qoi",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum PngCompression {
+    Fast,
+    #[default]
+    Default,
+    Best,
+}
+
+pub fn parse_png_compression(s: &str) -> Result<PngCompression, String> {
+    match s.to_lowercase().as_str() {
+        "fast" => Ok(PngCompression::Fast),
+        "default" => Ok(PngCompression::Default),
+        "best" => Ok(PngCompression::Best),
+        _ => Err(format!("Invalid PNG compression '{}'. Use: fast, default, best", s)),
+    }
+}
+
+impl PngCompression {
+    pub(crate) fn to_png(self) -> png::Compression {
+        match self {
+            PngCompression::Fast => png::Compression::Fast,
+            PngCompression::Default => png::Compression::Default,
+            PngCompression::Best => png::Compression::Best,
+        }
+    }
+}
+
+/// How rendered images are written. `quality` (1-100) applies to JPEG and
+/// WebP; WebP at 100 is lossless.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct RasterOptions {
+    pub format: RasterFormat,
+    pub quality: Option<u8>,
+    pub png_compression: PngCompression,
+}
+
+/// Encodes `img` as `options.format`. `metadata` is embedded in PNGs only.
+/// Formats whose codec feature is disabled fail with `image`'s
+/// unsupported-format error.
+pub fn encode(img: &RgbImage, options: &RasterOptions, metadata: &Metadata) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    let format = match options.format {
+        RasterFormat::Png => return metadata::encode_png(img, metadata, options.png_compression),
+        RasterFormat::Jpeg => return encode_jpeg(img, options.quality.unwrap_or(DEFAULT_JPEG_QUALITY)),
+        RasterFormat::Webp => match options.quality.unwrap_or(DEFAULT_WEBP_QUALITY) {
+            100 => ImageFormat::WebP,
+            quality => return encode_lossy_webp(img, quality),
+        },
+        RasterFormat::Bmp => ImageFormat::Bmp,
+        RasterFormat::Tiff => ImageFormat::Tiff,
+        RasterFormat::Qoi => ImageFormat::Qoi,
+    };
+    let mut bytes = Cursor::new(Vec::new());
+    DynamicImage::ImageRgb8(img.clone()).write_to(&mut bytes, format)?;
+    Ok(bytes.into_inner())
+}
+
+#[cfg(feature = "jpeg")]
+fn encode_jpeg(img: &RgbImage, quality: u8) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    let mut bytes = Vec::new();
+    image::codecs::jpeg::JpegEncoder::new_with_quality(&mut bytes, quality).encode_image(img)?;
+    Ok(bytes)
+}
+
+#[cfg(not(feature = "jpeg"))]
+fn encode_jpeg(_img: &RgbImage, _quality: u8) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    Err("JPEG output needs bitify built with the 'jpeg' feature".into())
+}
+
+/// `image` deprecates its libwebp-backed lossy encoder but has no other.
+#[cfg(feature = "webp-encoder")]
+#[allow(deprecated)]
+fn encode_lossy_webp(img: &RgbImage, quality: u8) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    use image::ImageEncoder;
+    use image::codecs::webp::{WebPEncoder, WebPQuality};
+
+    let mut bytes = Vec::new();
+    WebPEncoder::new_with_quality(&mut bytes, WebPQuality::lossy(quality)).write_image(img.as_raw(), img.width(), img.height(), image::ColorType::Rgb8)?;
+    Ok(bytes)
+}
+
+#[cfg(not(feature = "webp-encoder"))]
+fn encode_lossy_webp(_img: &RgbImage, _quality: u8) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    Err("Lossy WebP needs bitify built with the 'webp-encoder' feature; use --quality 100 for lossless".into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ConvertOptions, DensityPreset};
+
+    const FORMATS: [RasterFormat; 6] =
+        [RasterFormat::Png, RasterFormat::Jpeg, RasterFormat::Webp, RasterFormat::Bmp, RasterFormat::Tiff, RasterFormat::Qoi];
+
+    /// A busy image, so lossy quality changes show in the encoded size.
+    fn sample() -> RgbImage {
+        RgbImage::from_fn(48, 36, |x, y| image::Rgb([(x * 5) as u8, (y * 7) as u8, ((x ^ y) * 16) as u8]))
+    }
+
+    fn encode_as(format: RasterFormat, quality: Option<u8>, png_compression: PngCompression) -> Vec<u8> {
+        let metadata = Metadata::new("-", &ConvertOptions::new(DensityPreset::Medium));
+        encode(&sample(), &RasterOptions { format, quality, png_compression }, &metadata).unwrap()
+    }
+
+    #[test]
+    fn formats_are_named_by_extension() {
+        for format in FORMATS {
+            assert_eq!(parse_raster_format(format.extension()), Ok(format));
+            assert_eq!(RasterFormat::from_path(Path::new(&format!("art.{}", format.extension()))), Some(format));
+        }
+        assert_eq!(RasterFormat::from_path(Path::new("art.JPEG")), Some(RasterFormat::Jpeg));
+        assert_eq!(RasterFormat::from_path(Path::new("art.tif")), Some(RasterFormat::Tiff));
+        assert_eq!(RasterFormat::from_path(Path::new("art.txt")), None);
+        assert_eq!(RasterFormat::from_path(Path::new("art")), None);
+        assert!(parse_png_compression("max").is_err());
+    }
+
+    #[test]
+    #[cfg(all(feature = "all-codecs", feature = "webp-encoder"))]
+    fn every_format_decodes_back() {
+        for format in FORMATS {
+            let bytes = encode_as(format, None, PngCompression::Default);
+            let img = image::load_from_memory(&bytes).unwrap_or_else(|e| panic!("{:?}: {}", format, e));
+            assert_eq!((img.width(), img.height()), (48, 36), "{:?}", format);
+            if !matches!(format, RasterFormat::Jpeg | RasterFormat::Webp) {
+                assert_eq!(img.to_rgb8(), sample(), "{:?} is lossless", format);
+            }
+        }
+    }
+
+    #[test]
+    #[cfg(all(feature = "all-codecs", feature = "webp-encoder"))]
+    fn quality_trades_size_for_fidelity() {
+        let low = encode_as(RasterFormat::Jpeg, Some(10), PngCompression::Default);
+        assert!(low.len() < encode_as(RasterFormat::Jpeg, Some(95), PngCompression::Default).len());
+        let low = encode_as(RasterFormat::Webp, Some(10), PngCompression::Default);
+        assert!(low.len() < encode_as(RasterFormat::Webp, Some(90), PngCompression::Default).len());
+
+        let lossless = encode_as(RasterFormat::Webp, Some(100), PngCompression::Default);
+        assert_eq!(image::load_from_memory(&lossless).unwrap().to_rgb8(), sample());
+    }
+
+    #[test]
+    fn png_compression_keeps_the_pixels() {
+        let fast = encode_as(RasterFormat::Png, None, PngCompression::Fast);
+        let best = encode_as(RasterFormat::Png, None, PngCompression::Best);
+        assert!(best.len() <= fast.len());
+        assert_eq!(image::load_from_memory(&fast).unwrap().to_rgb8(), image::load_from_memory(&best).unwrap().to_rgb8());
+    }
+}
@@ -0,0 +1,694 @@
+//! Rasterizes character grids into images, one glyph per cell.
+
+use crate::font::Font;
+use crate::{AsciiPixel, ConvertOptions, ImageSize};
+use image::{Rgb, RgbImage};
+
+pub fn render_ascii_png(ascii_data: &[Vec<AsciiPixel>], options: &ConvertOptions) -> RgbImage {
+    fit_cells(ascii_data, &Font::Builtin, rendered_size(ascii_data, options), options.background())
+}
+
+/// The pixel size `render_ascii_png` produces for `ascii_data`.
+pub fn rendered_size(ascii_data: &[Vec<AsciiPixel>], options: &ConvertOptions) -> (u32, u32) {
+    match options.image_size {
+        Some(ImageSize::Pixels(width, height)) => (width, height),
+        _ => {
+            let (cell_width, cell_height) = options.cell_size.unwrap_or(Font::Builtin.size());
+            (grid_columns(ascii_data) * cell_width, ascii_data.len() as u32 * cell_height)
+        }
+    }
+}
+
+/// Draws each character's glyph into a `cell_size` cell, scaling the font's
+/// bitmaps with nearest-neighbour sampling when the sizes differ.
+pub fn render_cells(ascii_data: &[Vec<AsciiPixel>], font: &Font, cell_size: (u32, u32), background: Rgb<u8>) -> RgbImage {
+    let (char_width, char_height) = cell_size;
+    let size = (grid_columns(ascii_data) * char_width, ascii_data.len() as u32 * char_height);
+    fit_cells(ascii_data, font, size, background)
+}
+
+fn grid_columns(ascii_data: &[Vec<AsciiPixel>]) -> u32 {
+    ascii_data.first().map_or(0, Vec::len) as u32
+}
+
+/// Divides an image of `size` pixels among the cells. Cells differ by at
+/// most a pixel when the size does not divide evenly. An empty grid gives
+/// a blank image.
+fn fit_cells(ascii_data: &[Vec<AsciiPixel>], font: &Font, size: (u32, u32), background: Rgb<u8>) -> RgbImage {
+    let (img_width, img_height) = size;
+    let (font_width, font_height) = font.size();
+    let (columns, rows) = (grid_columns(ascii_data), ascii_data.len() as u32);
+    
+    let mut img = RgbImage::from_pixel(img_width, img_height, background);
+    if columns == 0 || rows == 0 {
+        return img;
+    }
+    
+    for (row_idx, row) in ascii_data.iter().enumerate() {
+        let base_y = row_idx as u32 * img_height / rows;
+        let char_height = (row_idx as u32 + 1) * img_height / rows - base_y;
+        for (col_idx, ascii_pixel) in row.iter().enumerate() {
+            let base_x = col_idx as u32 * img_width / columns;
+            let char_width = (col_idx as u32 + 1) * img_width / columns - base_x;
+            
+            let Some(pattern) = font.glyph(ascii_pixel.character) else {
+                continue;
+            };
+            let color = Rgb([ascii_pixel.color.0, ascii_pixel.color.1, ascii_pixel.color.2]);
+            
+            for py in 0..char_height {
+                let font_y = py * font_height / char_height;
+                for px in 0..char_width {
+                    let font_x = px * font_width / char_width;
+                    if pattern[(font_y * font_width + font_x) as usize] {
+                        img.put_pixel(base_x + px, base_y + py, color);
+                    }
+                }
+            }
+        }
+    }
+    
+    img
+}
+
+/// The built-in glyph for `ch`; characters it lacks draw blank.
+pub fn get_char_pattern(ch: char) -> &'static [[bool; 8]; 12] {
+    builtin_pattern(ch).unwrap_or(&SPACE)
+}
+
+pub(crate) fn builtin_pattern(ch: char) -> Option<&'static [[bool; 8]; 12]> {
+    let pattern = match ch {
+        ' ' => &SPACE,
+        '.' => &DOT,
+        ':' => &COLON,
+        '-' => &DASH,
+        '=' => &EQUALS,
+        '+' => &PLUS,
+        '*' => &ASTERISK,
+        '#' => &HASH,
+        '%' => &PERCENT,
+        '@' => &AT,
+        '\'' => &DOT,
+        '`' => &DOT,
+        '^' => &CARET,
+        '"' => &COLON,
+        ',' => &DOT,
+        ';' => &COLON,
+        'I' => &PIPE_PATTERN,
+        'l' => &PIPE_PATTERN,
+        '!' => &PIPE_PATTERN,
+        'i' => &DOT,
+        '>' => &GREATER,
+        '<' => &LESS,
+        '~' => &TILDE,
+        '_' => &UNDERSCORE,
+        '?' => &QUESTION,
+        ']' => &BRACKET_RIGHT,
+        '[' => &BRACKET_LEFT,
+        '}' => &BRACKET_RIGHT,
+        '{' => &BRACKET_LEFT,
+        '1' => &PIPE_PATTERN,
+        ')' => &PAREN_RIGHT,
+        '(' => &PAREN_LEFT,
+        '|' => &PIPE_PATTERN,
+        '\\' => &BACKSLASH,
+        '/' => &SLASH,
+        't' | 'f' | 'j' | 'r' | 'x' | 'n' | 'u' | 'v' | 'c' | 'z' => &SMALL_BLOCK,
+        'X' | 'Y' | 'U' | 'J' | 'C' | 'L' | 'Q' | 'O' | 'Z' => &MEDIUM_BLOCK,
+        '0' => &ZERO,
+        'm' | 'w' | 'q' | 'p' | 'd' | 'b' | 'k' | 'h' | 'a' | 'o' => &SMALL_BLOCK,
+        'M' | 'W' | 'B' | 'A' | 'G' | 'H' | 'K' | 'P' | 'R' | 'S' | 'T' | 'V' => &LARGE_BLOCK,
+        '&' | '8' | '$' => &LARGE_BLOCK,
+        'g' | 's' | 'y' | 'e' | 'F' | 'D' | 'N' => &MEDIUM_BLOCK,
+        '2' | '3' | '4' | '5' | '6' | '7' | '9' | 'E' => &MEDIUM_BLOCK,
+        // Block elements and shades, the bulk of CP437 ANSI art.
+        '█' => &FULL_BLOCK,
+        '▀' => &UPPER_HALF,
+        '▄' => &LOWER_HALF,
+        '▌' => &LEFT_HALF,
+        '▐' => &RIGHT_HALF,
+        '■' => &SQUARE,
+        '░' => &LIGHT_SHADE,
+        '▒' => &MEDIUM_SHADE,
+        '▓' => &DARK_SHADE,
+        // Double and mixed box-drawing lines are drawn single.
+        '─' | '═' => &BOX_LINES[LEFT | RIGHT],
+        '│' | '║' => &BOX_LINES[UP | DOWN],
+        '┌' | '╔' | '╒' | '╓' => &BOX_LINES[DOWN | RIGHT],
+        '┐' | '╗' | '╕' | '╖' => &BOX_LINES[DOWN | LEFT],
+        '└' | '╚' | '╘' | '╙' => &BOX_LINES[UP | RIGHT],
+        '┘' | '╝' | '╛' | '╜' => &BOX_LINES[UP | LEFT],
+        '├' | '╠' | '╞' | '╟' => &BOX_LINES[UP | DOWN | RIGHT],
+        '┤' | '╣' | '╡' | '╢' => &BOX_LINES[UP | DOWN | LEFT],
+        '┬' | '╦' | '╤' | '╥' => &BOX_LINES[DOWN | LEFT | RIGHT],
+        '┴' | '╩' | '╧' | '╨' => &BOX_LINES[UP | LEFT | RIGHT],
+        '┼' | '╬' | '╪' | '╫' => &BOX_LINES[UP | DOWN | LEFT | RIGHT],
+        _ => return None,
+    };
+    Some(pattern)
+}
+
+/// A pattern with the pixels from `x0..x1` and `y0..y1` set.
+const fn rectangle(x0: usize, x1: usize, y0: usize, y1: usize) -> [[bool; 8]; 12] {
+    let mut pattern = [[false; 8]; 12];
+    let mut y = y0;
+    while y < y1 {
+        let mut x = x0;
+        while x < x1 {
+            pattern[y][x] = true;
+            x += 1;
+        }
+        y += 1;
+    }
+    pattern
+}
+
+/// A dither with `level` (1-3) quarters of the pixels set.
+const fn shade(level: usize) -> [[bool; 8]; 12] {
+    let mut pattern = [[false; 8]; 12];
+    let mut y = 0;
+    while y < 12 {
+        let mut x = 0;
+        while x < 8 {
+            pattern[y][x] = match level {
+                1 => x % 2 == 0 && y % 2 == 0,
+                2 => (x + y) % 2 == 0,
+                _ => !(x % 2 == 1 && y % 2 == 1),
+            };
+            x += 1;
+        }
+        y += 1;
+    }
+    pattern
+}
+
+const UP: usize = 1;
+const DOWN: usize = 2;
+const LEFT: usize = 4;
+const RIGHT: usize = 8;
+
+/// Two-pixel box-drawing strokes from the cell center toward each side
+/// set in the index.
+const BOX_LINES: [[[bool; 8]; 12]; 16] = {
+    let mut patterns = [[[false; 8]; 12]; 16];
+    let mut arms = 1;
+    while arms < 16 {
+        let (x0, x1) = (if arms & LEFT != 0 { 0 } else { 3 }, if arms & RIGHT != 0 { 8 } else { 5 });
+        let (y0, y1) = (if arms & UP != 0 { 0 } else { 5 }, if arms & DOWN != 0 { 12 } else { 7 });
+        let horizontal = rectangle(x0, x1, 5, 7);
+        let vertical = rectangle(3, 5, y0, y1);
+        let mut y = 0;
+        while y < 12 {
+            let mut x = 0;
+            while x < 8 {
+                let across = arms & (LEFT | RIGHT) != 0 && horizontal[y][x];
+                let along = arms & (UP | DOWN) != 0 && vertical[y][x];
+                patterns[arms][y][x] = across || along;
+                x += 1;
+            }
+            y += 1;
+        }
+        arms += 1;
+    }
+    patterns
+};
+
+const FULL_BLOCK: [[bool; 8]; 12] = rectangle(0, 8, 0, 12);
+const UPPER_HALF: [[bool; 8]; 12] = rectangle(0, 8, 0, 6);
+const LOWER_HALF: [[bool; 8]; 12] = rectangle(0, 8, 6, 12);
+const LEFT_HALF: [[bool; 8]; 12] = rectangle(0, 4, 0, 12);
+const RIGHT_HALF: [[bool; 8]; 12] = rectangle(4, 8, 0, 12);
+const SQUARE: [[bool; 8]; 12] = rectangle(1, 7, 3, 9);
+const LIGHT_SHADE: [[bool; 8]; 12] = shade(1);
+const MEDIUM_SHADE: [[bool; 8]; 12] = shade(2);
+const DARK_SHADE: [[bool; 8]; 12] = shade(3);
+
+const SPACE: [[bool; 8]; 12] = [
+    [false; 8],
+    [false; 8],
+    [false; 8],
+    [false; 8],
+    [false; 8],
+    [false; 8],
+    [false; 8],
+    [false; 8],
+    [false; 8],
+    [false; 8],
+    [false; 8],
+    [false; 8],
+];
+
+const DOT: [[bool; 8]; 12] = [
+    [false; 8],
+    [false; 8],
+    [false; 8],
+    [false; 8],
+    [false; 8],
+    [false; 8],
+    [false; 8],
+    [false; 8],
+    [false; 8],
+    [false, false, false, true, true, false, false, false],
+    [false, false, false, true, true, false, false, false],
+    [false; 8],
+];
+
+const COLON: [[bool; 8]; 12] = [
+    [false; 8],
+    [false; 8],
+    [false; 8],
+    [false, false, false, true, true, false, false, false],
+    [false, false, false, true, true, false, false, false],
+    [false; 8],
+    [false; 8],
+    [false, false, false, true, true, false, false, false],
+    [false, false, false, true, true, false, false, false],
+    [false; 8],
+    [false; 8],
+    [false; 8],
+];
+
+const DASH: [[bool; 8]; 12] = [
+    [false; 8],
+    [false; 8],
+    [false; 8],
+    [false; 8],
+    [false; 8],
+    [false; 8],
+    [false, true, true, true, true, true, true, false],
+    [false; 8],
+    [false; 8],
+    [false; 8],
+    [false; 8],
+    [false; 8],
+];
+
+const EQUALS: [[bool; 8]; 12] = [
+    [false; 8],
+    [false; 8],
+    [false; 8],
+    [false; 8],
+    [false, true, true, true, true, true, true, false],
+    [false; 8],
+    [false; 8],
+    [false, true, true, true, true, true, true, false],
+    [false; 8],
+    [false; 8],
+    [false; 8],
+    [false; 8],
+];
+
+const PLUS: [[bool; 8]; 12] = [
+    [false; 8],
+    [false; 8],
+    [false; 8],
+    [false, false, false, true, true, false, false, false],
+    [false, false, false, true, true, false, false, false],
+    [false, true, true, true, true, true, true, false],
+    [false, true, true, true, true, true, true, false],
+    [false, false, false, true, true, false, false, false],
+    [false, false, false, true, true, false, false, false],
+    [false; 8],
+    [false; 8],
+    [false; 8],
+];
+
+const ASTERISK: [[bool; 8]; 12] = [
+    [false; 8],
+    [false; 8],
+    [false, false, true, false, false, true, false, false],
+    [false, false, false, true, true, false, false, false],
+    [false, true, true, true, true, true, true, false],
+    [false, false, false, true, true, false, false, false],
+    [false, false, true, false, false, true, false, false],
+    [false; 8],
+    [false; 8],
+    [false; 8],
+    [false; 8],
+    [false; 8],
+];
+
+const HASH: [[bool; 8]; 12] = [
+    [false; 8],
+    [false, false, true, false, true, false, false, false],
+    [false, false, true, false, true, false, false, false],
+    [false, true, true, true, true, true, true, false],
+    [false, false, true, false, true, false, false, false],
+    [false, false, true, false, true, false, false, false],
+    [false, true, true, true, true, true, true, false],
+    [false, false, true, false, true, false, false, false],
+    [false, false, true, false, true, false, false, false],
+    [false; 8],
+    [false; 8],
+    [false; 8],
+];
+
+const PERCENT: [[bool; 8]; 12] = [
+    [false; 8],
+    [false, true, true, false, false, false, true, false],
+    [false, true, true, false, false, true, false, false],
+    [false, false, false, false, true, false, false, false],
+    [false, false, false, true, false, false, false, false],
+    [false, false, true, false, false, false, false, false],
+    [false, true, false, false, false, false, false, false],
+    [false, true, false, false, true, true, false, false],
+    [false, false, false, false, true, true, false, false],
+    [false; 8],
+    [false; 8],
+    [false; 8],
+];
+
+const AT: [[bool; 8]; 12] = [
+    [false, false, true, true, true, true, false, false],
+    [false, true, false, false, false, false, true, false],
+    [false, true, false, true, true, false, true, false],
+    [false, true, true, false, false, true, true, false],
+    [false, true, true, false, false, true, true, false],
+    [false, true, true, false, false, true, true, false],
+    [false, true, false, true, true, true, false, false],
+    [false, true, false, false, false, false, false, false],
+    [false, false, true, true, true, true, false, false],
+    [false; 8],
+    [false; 8],
+    [false; 8],
+];
+
+const CARET: [[bool; 8]; 12] = [
+    [false; 8],
+    [false, false, false, true, true, false, false, false],
+    [false, false, true, false, false, true, false, false],
+    [false, true, false, false, false, false, true, false],
+    [false; 8],
+    [false; 8],
+    [false; 8],
+    [false; 8],
+    [false; 8],
+    [false; 8],
+    [false; 8],
+    [false; 8],
+];
+
+const GREATER: [[bool; 8]; 12] = [
+    [false; 8],
+    [false; 8],
+    [false, false, true, false, false, false, false, false],
+    [false, false, false, true, false, false, false, false],
+    [false, false, false, false, true, false, false, false],
+    [false, false, false, false, false, true, false, false],
+    [false, false, false, false, true, false, false, false],
+    [false, false, false, true, false, false, false, false],
+    [false, false, true, false, false, false, false, false],
+    [false; 8],
+    [false; 8],
+    [false; 8],
+];
+
+const LESS: [[bool; 8]; 12] = [
+    [false; 8],
+    [false; 8],
+    [false, false, false, false, false, true, false, false],
+    [false, false, false, false, true, false, false, false],
+    [false, false, false, true, false, false, false, false],
+    [false, false, true, false, false, false, false, false],
+    [false, false, false, true, false, false, false, false],
+    [false, false, false, false, true, false, false, false],
+    [false, false, false, false, false, true, false, false],
+    [false; 8],
+    [false; 8],
+    [false; 8],
+];
+
+const TILDE: [[bool; 8]; 12] = [
+    [false; 8],
+    [false; 8],
+    [false; 8],
+    [false; 8],
+    [false, false, true, true, false, false, true, false],
+    [false, true, false, false, true, true, false, false],
+    [false; 8],
+    [false; 8],
+    [false; 8],
+    [false; 8],
+    [false; 8],
+    [false; 8],
+];
+
+const UNDERSCORE: [[bool; 8]; 12] = [
+    [false; 8],
+    [false; 8],
+    [false; 8],
+    [false; 8],
+    [false; 8],
+    [false; 8],
+    [false; 8],
+    [false; 8],
+    [false; 8],
+    [false; 8],
+    [false; 8],
+    [true, true, true, true, true, true, true, true],
+];
+
+const QUESTION: [[bool; 8]; 12] = [
+    [false; 8],
+    [false, false, true, true, true, true, false, false],
+    [false, true, false, false, false, false, true, false],
+    [false, false, false, false, false, true, false, false],
+    [false, false, false, false, true, false, false, false],
+    [false, false, false, true, false, false, false, false],
+    [false, false, false, true, false, false, false, false],
+    [false; 8],
+    [false, false, false, true, false, false, false, false],
+    [false; 8],
+    [false; 8],
+    [false; 8],
+];
+
+const BRACKET_RIGHT: [[bool; 8]; 12] = [
+    [false, true, true, true, false, false, false, false],
+    [false, false, false, true, false, false, false, false],
+    [false, false, false, true, false, false, false, false],
+    [false, false, false, true, false, false, false, false],
+    [false, false, false, true, false, false, false, false],
+    [false, false, false, true, false, false, false, false],
+    [false, false, false, true, false, false, false, false],
+    [false, false, false, true, false, false, false, false],
+    [false, false, false, true, false, false, false, false],
+    [false, true, true, true, false, false, false, false],
+    [false; 8],
+    [false; 8],
+];
+
+const BRACKET_LEFT: [[bool; 8]; 12] = [
+    [false, false, false, true, true, true, false, false],
+    [false, false, false, true, false, false, false, false],
+    [false, false, false, true, false, false, false, false],
+    [false, false, false, true, false, false, false, false],
+    [false, false, false, true, false, false, false, false],
+    [false, false, false, true, false, false, false, false],
+    [false, false, false, true, false, false, false, false],
+    [false, false, false, true, false, false, false, false],
+    [false, false, false, true, false, false, false, false],
+    [false, false, false, true, true, true, false, false],
+    [false; 8],
+    [false; 8],
+];
+
+const PAREN_RIGHT: [[bool; 8]; 12] = [
+    [false, false, true, false, false, false, false, false],
+    [false, false, false, true, false, false, false, false],
+    [false, false, false, false, true, false, false, false],
+    [false, false, false, false, true, false, false, false],
+    [false, false, false, false, true, false, false, false],
+    [false, false, false, false, true, false, false, false],
+    [false, false, false, false, true, false, false, false],
+    [false, false, false, false, true, false, false, false],
+    [false, false, false, true, false, false, false, false],
+    [false, false, true, false, false, false, false, false],
+    [false; 8],
+    [false; 8],
+];
+
+const PAREN_LEFT: [[bool; 8]; 12] = [
+    [false, false, false, false, true, false, false, false],
+    [false, false, false, true, false, false, false, false],
+    [false, false, true, false, false, false, false, false],
+    [false, false, true, false, false, false, false, false],
+    [false, false, true, false, false, false, false, false],
+    [false, false, true, false, false, false, false, false],
+    [false, false, true, false, false, false, false, false],
+    [false, false, true, false, false, false, false, false],
+    [false, false, false, true, false, false, false, false],
+    [false, false, false, false, true, false, false, false],
+    [false; 8],
+    [false; 8],
+];
+
+const PIPE_PATTERN: [[bool; 8]; 12] = [
+    [false, false, false, true, true, false, false, false],
+    [false, false, false, true, true, false, false, false],
+    [false, false, false, true, true, false, false, false],
+    [false, false, false, true, true, false, false, false],
+    [false, false, false, true, true, false, false, false],
+    [false, false, false, true, true, false, false, false],
+    [false, false, false, true, true, false, false, false],
+    [false, false, false, true, true, false, false, false],
+    [false, false, false, true, true, false, false, false],
+    [false, false, false, true, true, false, false, false],
+    [false; 8],
+    [false; 8],
+];
+
+const BACKSLASH: [[bool; 8]; 12] = [
+    [false, true, false, false, false, false, false, false],
+    [false, true, false, false, false, false, false, false],
+    [false, false, true, false, false, false, false, false],
+    [false, false, true, false, false, false, false, false],
+    [false, false, false, true, false, false, false, false],
+    [false, false, false, true, false, false, false, false],
+    [false, false, false, false, true, false, false, false],
+    [false, false, false, false, true, false, false, false],
+    [false, false, false, false, false, true, false, false],
+    [false, false, false, false, false, true, false, false],
+    [false; 8],
+    [false; 8],
+];
+
+const SLASH: [[bool; 8]; 12] = [
+    [false, false, false, false, false, true, false, false],
+    [false, false, false, false, false, true, false, false],
+    [false, false, false, false, true, false, false, false],
+    [false, false, false, false, true, false, false, false],
+    [false, false, false, true, false, false, false, false],
+    [false, false, false, true, false, false, false, false],
+    [false, false, true, false, false, false, false, false],
+    [false, false, true, false, false, false, false, false],
+    [false, true, false, false, false, false, false, false],
+    [false, true, false, false, false, false, false, false],
+    [false; 8],
+    [false; 8],
+];
+
+const ZERO: [[bool; 8]; 12] = [
+    [false, false, true, true, true, true, false, false],
+    [false, true, false, false, false, false, true, false],
+    [false, true, false, false, false, true, true, false],
+    [false, true, false, false, true, false, true, false],
+    [false, true, false, true, false, false, true, false],
+    [false, true, true, false, false, false, true, false],
+    [false, true, false, false, false, false, true, false],
+    [false, false, true, true, true, true, false, false],
+    [false; 8],
+    [false; 8],
+    [false; 8],
+    [false; 8],
+];
+
+const SMALL_BLOCK: [[bool; 8]; 12] = [
+    [false; 8],
+    [false; 8],
+    [false; 8],
+    [false; 8],
+    [false, false, true, true, true, true, false, false],
+    [false, false, true, true, true, true, false, false],
+    [false, false, true, true, true, true, false, false],
+    [false, false, true, true, true, true, false, false],
+    [false; 8],
+    [false; 8],
+    [false; 8],
+    [false; 8],
+];
+
+const MEDIUM_BLOCK: [[bool; 8]; 12] = [
+    [false; 8],
+    [false; 8],
+    [false, true, true, true, true, true, true, false],
+    [false, true, true, true, true, true, true, false],
+    [false, true, true, true, true, true, true, false],
+    [false, true, true, true, true, true, true, false],
+    [false, true, true, true, true, true, true, false],
+    [false, true, true, true, true, true, true, false],
+    [false; 8],
+    [false; 8],
+    [false; 8],
+    [false; 8],
+];
+
+const LARGE_BLOCK: [[bool; 8]; 12] = [
+    [false; 8],
+    [true, true, true, true, true, true, true, true],
+    [true, true, true, true, true, true, true, true],
+    [true, true, true, true, true, true, true, true],
+    [true, true, true, true, true, true, true, true],
+    [true, true, true, true, true, true, true, true],
+    [true, true, true, true, true, true, true, true],
+    [true, true, true, true, true, true, true, true],
+    [true, true, true, true, true, true, true, true],
+    [true, true, true, true, true, true, true, true],
+    [false; 8],
+    [false; 8],
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lit(pattern: &[[bool; 8]; 12]) -> usize {
+        pattern.as_flattened().iter().filter(|&&on| on).count()
+    }
+
+    #[test]
+    fn block_elements_fill_their_share_of_the_cell() {
+        assert_eq!(lit(get_char_pattern('█')), 96);
+        for half in ['▀', '▄', '▌', '▐'] {
+            assert_eq!(lit(get_char_pattern(half)), 48, "{}", half);
+        }
+        assert!(get_char_pattern('▀')[0].iter().all(|&on| on) && !get_char_pattern('▀')[11][0]);
+        assert_eq!([lit(get_char_pattern('░')), lit(get_char_pattern('▒')), lit(get_char_pattern('▓'))], [24, 48, 72]);
+    }
+
+    #[test]
+    fn box_drawing_lines_reach_the_cell_edges() {
+        let horizontal = get_char_pattern('═');
+        assert!(horizontal[5].iter().all(|&on| on) && !horizontal[0][3]);
+        let corner = get_char_pattern('┌');
+        assert!(corner[11][3] && corner[5][7] && !corner[0][3] && !corner[5][0]);
+    }
+
+    #[test]
+    fn unknown_characters_have_no_builtin_glyph() {
+        assert!(builtin_pattern('α').is_none());
+        assert_eq!(lit(get_char_pattern('α')), 0);
+        assert!(!Font::Builtin.has_glyph('α'));
+        assert!(Font::Builtin.has_glyph('▓') && Font::Builtin.has_glyph(' '));
+    }
+
+    #[test]
+    fn cells_scale_to_the_requested_size() {
+        let grid = vec![vec![AsciiPixel { character: '█', color: (9, 8, 7) }, AsciiPixel { character: ' ', color: (9, 8, 7) }]];
+        let img = render_cells(&grid, &Font::Builtin, (16, 24), Rgb([0, 0, 0]));
+        assert_eq!(img.dimensions(), (32, 24));
+        assert_eq!(img.get_pixel(15, 23), &Rgb([9, 8, 7]));
+        assert_eq!(img.get_pixel(16, 0), &Rgb([0, 0, 0]));
+    }
+
+    #[test]
+    fn scaled_glyphs_are_whole_pixel_enlargements() {
+        let grid = vec![vec![AsciiPixel { character: 'A', color: (255, 255, 255) }, AsciiPixel { character: '%', color: (1, 2, 3) }]];
+        let background = Rgb([0, 0, 0]);
+        let native = render_cells(&grid, &Font::Builtin, Font::Builtin.size(), background);
+        let scaled = render_cells(&grid, &Font::Builtin, (24, 36), background);
+        assert_eq!(scaled, image::imageops::resize(&native, 48, 36, image::imageops::FilterType::Nearest));
+    }
+
+    #[test]
+    fn rendered_size_follows_cells_or_a_fixed_size() {
+        let grid = vec![vec![AsciiPixel { character: '#', color: (0, 0, 0) }; 5]; 3];
+        let mut options = ConvertOptions::new(crate::DensityPreset::Low);
+        assert_eq!(rendered_size(&grid, &options), (40, 36));
+        options.cell_size = Some((10, 20));
+        assert_eq!(rendered_size(&grid, &options), (50, 60));
+        assert_eq!(render_ascii_png(&grid, &options).dimensions(), (50, 60));
+        options.image_size = Some(ImageSize::Pixels(123, 45));
+        assert_eq!(render_ascii_png(&grid, &options).dimensions(), (123, 45));
+    }
+}
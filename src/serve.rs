@@ -0,0 +1,527 @@
+use crate::config::Config;
+use crate::{OutputFormat, encode_grid, options_from_flags, parse_output_format};
+use bitify::ansi::DEFAULT_COLOR_TOLERANCE;
+use bitify::raster::{self, RasterOptions};
+use bitify::input::{self, Container};
+use bitify::{convert_image, decode_image, rendered_size};
+use std::collections::BTreeMap;
+use std::io::{self, Cursor, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+
+const MAX_HEADER_BYTES: usize = 16 * 1024;
+/// Uploads are decoded in full, so very large images are refused before
+/// decoding even when the encoded file is small.
+const MAX_PIXELS: u64 = 64_000_000;
+
+/// Conversion flags accepted as query parameters (`_` may stand for `-`).
+const VALUE_PARAMS: &[&str] = &[
+    "width", "density", "preset", "charset", "filter", "luminance", "brightness", "contrast", "gamma",
+    "color", "palette", "rotate", "flip", "crop", "trim-tolerance", "scale", "cell-size",
+    "png-size",
+];
+const SWITCH_PARAMS: &[&str] = &["auto-levels", "equalize", "invert", "no-exif", "trim", "match-source"];
+
+const USAGE: &str = "bitify server
+
+POST /convert    Convert the request body: a raw image, or multipart/form-data
+                 with the image in a file field
+GET  /health     Liveness check
+
+Query parameters (or multipart form fields):
+  format         png (default), text, ansi, html, json
+  image-format   File type for format=png: png (default), jpeg, webp, bmp,
+                 tiff, qoi
+  quality        JPEG or WebP quality, 1-100
+  png-compression
+                 fast, default, best
+  tolerance      Color run tolerance for ansi output (default 8)
+  width, density, preset, charset, filter, luminance, brightness, contrast,
+  gamma, color, palette, rotate, flip, crop, trim-tolerance, scale, cell-size,
+  png-size       Same values as the command-line flags
+  auto-levels, equalize, invert, no-exif, trim, match-source
+                 Switches: 1/true/yes/on or 0/false/no/off
+
+Example:
+  curl --data-binary @photo.jpg 'http://localhost:8080/convert?density=high&format=text'
+";
+
+pub struct Limits {
+    /// Largest accepted request body in bytes.
+    pub max_upload: u64,
+    /// Time allowed to receive a whole request.
+    pub timeout: Duration,
+    pub max_width: u32,
+    pub max_connections: usize,
+}
+
+#[derive(Debug)]
+struct Response {
+    status: u16,
+    content_type: &'static str,
+    body: Vec<u8>,
+}
+
+impl Response {
+    fn ok(content_type: &'static str, body: Vec<u8>) -> Response {
+        Response { status: 200, content_type, body }
+    }
+
+    fn error(status: u16, message: impl Into<String>) -> Response {
+        let mut body = message.into().into_bytes();
+        body.push(b'\n');
+        Response { status, content_type: "text/plain; charset=utf-8", body }
+    }
+}
+
+fn reason(status: u16) -> &'static str {
+    match status {
+        100 => "Continue",
+        200 => "OK",
+        400 => "Bad Request",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        408 => "Request Timeout",
+        411 => "Length Required",
+        413 => "Content Too Large",
+        422 => "Unprocessable Content",
+        431 => "Request Header Fields Too Large",
+        500 => "Internal Server Error",
+        503 => "Service Unavailable",
+        _ => "",
+    }
+}
+
+struct Request {
+    method: String,
+    path: String,
+    query: String,
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+}
+
+impl Request {
+    fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+/// Serves conversions until the process is killed. Each connection gets a
+/// thread; connections beyond `max_connections` are turned away with 503.
+pub fn serve(bind: &str, port: u16, limits: &Limits, config: &Config) -> Result<(), Box<dyn std::error::Error>> {
+    let listener = TcpListener::bind((bind, port)).map_err(|e| format!("Could not listen on {}:{}: {}", bind, port, e))?;
+    eprintln!("Listening on http://{}", listener.local_addr()?);
+
+    let active = AtomicUsize::new(0);
+    thread::scope(|scope| {
+        for stream in listener.incoming() {
+            let Ok(mut stream) = stream else { continue };
+            if active.fetch_add(1, Ordering::SeqCst) >= limits.max_connections {
+                active.fetch_sub(1, Ordering::SeqCst);
+                let _ = respond(&mut stream, &Response::error(503, "Too many connections, try again later"));
+                continue;
+            }
+            let slot = Slot(&active);
+            scope.spawn(move || {
+                let _slot = slot;
+                handle(stream, limits, config);
+            });
+        }
+    });
+    Ok(())
+}
+
+/// A counted connection, released on drop so a panicking handler still
+/// frees its slot.
+struct Slot<'a>(&'a AtomicUsize);
+
+impl Drop for Slot<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+fn handle(mut stream: TcpStream, limits: &Limits, config: &Config) {
+    let started = Instant::now();
+    let peer = stream.peer_addr().map(|addr| addr.to_string()).unwrap_or_default();
+    let (line, response) = match read_request(&mut stream, started + limits.timeout, limits) {
+        Ok(request) => {
+            let line = format!("{} {}", request.method, request.path);
+            (line, route(&request, limits, config))
+        }
+        Err(response) => ("-".to_string(), response),
+    };
+    let _ = stream.set_write_timeout(Some(limits.timeout));
+    let _ = respond(&mut stream, &response);
+    eprintln!("{} {} {} {} bytes {}ms", peer, line, response.status, response.body.len(), started.elapsed().as_millis());
+}
+
+fn respond(stream: &mut TcpStream, response: &Response) -> io::Result<()> {
+    let mut bytes = format!(
+        "HTTP/1.1 {} {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        response.status,
+        reason(response.status),
+        response.content_type,
+        response.body.len()
+    )
+    .into_bytes();
+    bytes.extend_from_slice(&response.body);
+    stream.write_all(&bytes)?;
+    stream.flush()
+}
+
+/// Reads into `buffer` with whatever time is left before `deadline`.
+fn read_some(stream: &mut TcpStream, buffer: &mut [u8], deadline: Instant) -> Result<usize, Response> {
+    let timed_out = || Response::error(408, "Request took too long to arrive");
+    let remaining = deadline.saturating_duration_since(Instant::now());
+    if remaining.is_zero() {
+        return Err(timed_out());
+    }
+    stream.set_read_timeout(Some(remaining)).map_err(|e| Response::error(500, e.to_string()))?;
+    match stream.read(buffer) {
+        Ok(0) => Err(Response::error(400, "Connection closed mid-request")),
+        Ok(n) => Ok(n),
+        Err(e) if matches!(e.kind(), io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut) => Err(timed_out()),
+        Err(e) => Err(Response::error(400, e.to_string())),
+    }
+}
+
+fn read_request(stream: &mut TcpStream, deadline: Instant, limits: &Limits) -> Result<Request, Response> {
+    let mut buffer = Vec::new();
+    let mut chunk = [0u8; 8192];
+    let header_end = loop {
+        let end = buffer.windows(4).position(|w| w == b"\r\n\r\n");
+        if end.unwrap_or(buffer.len()) > MAX_HEADER_BYTES {
+            return Err(Response::error(431, "Request headers are too large"));
+        }
+        if let Some(end) = end {
+            break end;
+        }
+        let n = read_some(stream, &mut chunk, deadline)?;
+        buffer.extend_from_slice(&chunk[..n]);
+    };
+
+    let head = String::from_utf8_lossy(&buffer[..header_end]).into_owned();
+    let mut lines = head.split("\r\n");
+    let mut request_line = lines.next().unwrap_or_default().split(' ');
+    let (Some(method), Some(target)) = (request_line.next(), request_line.next()) else {
+        return Err(Response::error(400, "Malformed request line"));
+    };
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+    let headers = lines
+        .filter_map(|line| line.split_once(':'))
+        .map(|(key, value)| (key.trim().to_string(), value.trim().to_string()))
+        .collect();
+    let mut request = Request {
+        method: method.to_string(),
+        path: path.to_string(),
+        query: query.to_string(),
+        headers,
+        body: Vec::new(),
+    };
+
+    if request.header("Transfer-Encoding").is_some() {
+        return Err(Response::error(411, "Chunked uploads are not supported; send Content-Length"));
+    }
+    let length = match request.header("Content-Length") {
+        Some(value) => value.parse::<u64>().map_err(|_| Response::error(400, "Invalid Content-Length"))?,
+        None => 0,
+    };
+    if length > limits.max_upload {
+        return Err(Response::error(413, format!("Upload exceeds the limit of {} bytes", limits.max_upload)));
+    }
+    if length > 0 && request.header("Expect").is_some_and(|value| value.eq_ignore_ascii_case("100-continue")) {
+        let _ = stream.write_all(b"HTTP/1.1 100 Continue\r\n\r\n");
+    }
+
+    let length = length as usize;
+    request.body = buffer.split_off(header_end + 4);
+    request.body.truncate(length);
+    while request.body.len() < length {
+        let want = (length - request.body.len()).min(chunk.len());
+        let n = read_some(stream, &mut chunk[..want], deadline)?;
+        request.body.extend_from_slice(&chunk[..n]);
+    }
+    Ok(request)
+}
+
+fn route(request: &Request, limits: &Limits, config: &Config) -> Response {
+    match (request.method.as_str(), request.path.as_str()) {
+        ("GET", "/") => Response::ok("text/plain; charset=utf-8", USAGE.as_bytes().to_vec()),
+        ("GET", "/health") => Response::ok("text/plain; charset=utf-8", b"ok\n".to_vec()),
+        ("POST", "/" | "/convert") => convert(request, limits, config).unwrap_or_else(|response| response),
+        (_, "/" | "/convert" | "/health") => Response::error(405, format!("{} is not allowed here", request.method)),
+        _ => Response::error(404, format!("No such endpoint {}", request.path)),
+    }
+}
+
+fn percent_decode(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'+' => out.push(b' '),
+            b'%' if let Some(byte) = s.get(i + 1..i + 3).and_then(|hex| u8::from_str_radix(hex, 16).ok()) => {
+                out.push(byte);
+                i += 2;
+            }
+            byte => out.push(byte),
+        }
+        i += 1;
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+/// The text fields and first file of a multipart/form-data body.
+struct Multipart<'a> {
+    fields: Vec<(String, String)>,
+    file: Option<&'a [u8]>,
+}
+
+fn parse_multipart<'a>(body: &'a [u8], boundary: &str) -> Result<Multipart<'a>, Response> {
+    let delimiter = format!("--{}", boundary);
+    let delimiter = delimiter.as_bytes();
+    let find = |haystack: &[u8], needle: &[u8]| haystack.windows(needle.len()).position(|w| w == needle);
+
+    let mut fields = Vec::new();
+    let mut file = None;
+    let mut rest = match find(body, delimiter) {
+        Some(start) => &body[start + delimiter.len()..],
+        None => return Err(Response::error(400, "Multipart body has no parts")),
+    };
+    while !rest.starts_with(b"--") {
+        let end = find(rest, delimiter).ok_or_else(|| Response::error(400, "Unterminated multipart body"))?;
+        let part = rest[..end].strip_prefix(b"\r\n").unwrap_or(&rest[..end]);
+        let part = part.strip_suffix(b"\r\n").unwrap_or(part);
+        rest = &rest[end + delimiter.len()..];
+
+        let Some(split) = find(part, b"\r\n\r\n") else { continue };
+        let headers = String::from_utf8_lossy(&part[..split]);
+        let content = &part[split + 4..];
+        let disposition = headers
+            .lines()
+            .find(|line| line.to_ascii_lowercase().starts_with("content-disposition:"))
+            .unwrap_or_default();
+        let attribute = |name: &str| {
+            disposition
+                .split(';')
+                .filter_map(|pair| pair.trim().split_once('='))
+                .find(|(key, _)| key.eq_ignore_ascii_case(name))
+                .map(|(_, value)| value.trim_matches('"').to_string())
+        };
+        if attribute("filename").is_some() {
+            file.get_or_insert(content);
+        } else if let Some(name) = attribute("name") {
+            fields.push((name, String::from_utf8_lossy(content).into_owned()));
+        }
+    }
+    Ok(Multipart { fields, file })
+}
+
+fn parse_switch(key: &str, value: &str) -> Result<bool, Response> {
+    match value.to_lowercase().as_str() {
+        "" | "1" | "true" | "yes" | "on" => Ok(true),
+        "0" | "false" | "no" | "off" => Ok(false),
+        _ => Err(Response::error(400, format!("Invalid value '{}' for {}; use true or false", value, key))),
+    }
+}
+
+fn convert(request: &Request, limits: &Limits, config: &Config) -> Result<Response, Response> {
+    let mut params = BTreeMap::new();
+    let mut image = request.body.as_slice();
+    let content_type = request.header("Content-Type").unwrap_or_default();
+    if content_type.to_lowercase().starts_with("multipart/form-data") {
+        let boundary = content_type
+            .split(';')
+            .filter_map(|pair| pair.trim().split_once('='))
+            .find(|(key, _)| key.eq_ignore_ascii_case("boundary"))
+            .map(|(_, value)| value.trim_matches('"'))
+            .ok_or_else(|| Response::error(400, "Multipart upload without a boundary"))?;
+        let multipart = parse_multipart(&request.body, boundary)?;
+        params.extend(multipart.fields);
+        image = multipart.file.ok_or_else(|| Response::error(400, "Multipart upload has no file field"))?;
+    }
+    // Query parameters take precedence over form fields.
+    for pair in request.query.split('&').filter(|pair| !pair.is_empty()) {
+        let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+        params.insert(percent_decode(key).replace('_', "-"), percent_decode(value));
+    }
+    if image.is_empty() {
+        return Err(Response::error(400, "No image in the request body"));
+    }
+
+    let mut format = OutputFormat::Png;
+    let mut tolerance = DEFAULT_COLOR_TOLERANCE;
+    let mut raster = RasterOptions::default();
+    let mut flags = Vec::new();
+    for (key, value) in &params {
+        match key.as_str() {
+            "format" => format = parse_output_format(value).map_err(|e| Response::error(400, e))?,
+            "image-format" => raster.format = raster::parse_raster_format(value).map_err(|e| Response::error(400, e))?,
+            "quality" => {
+                raster.quality = Some(value.parse().ok().filter(|quality| (1..=100).contains(quality)).ok_or_else(|| {
+                    Response::error(400, format!("Invalid quality '{}' (1-100)", value))
+                })?);
+            }
+            "png-compression" => {
+                raster.png_compression = raster::parse_png_compression(value).map_err(|e| Response::error(400, e))?;
+            }
+            "tolerance" | "color-tolerance" => {
+                tolerance = value.parse().map_err(|_| Response::error(400, format!("Invalid tolerance '{}' (0-255)", value)))?;
+            }
+            key if SWITCH_PARAMS.contains(&key) => {
+                if parse_switch(key, value)? {
+                    flags.push(format!("--{}", key));
+                }
+            }
+            key if VALUE_PARAMS.contains(&key) => flags.push(format!("--{}={}", key, value)),
+            _ => return Err(Response::error(400, format!("Unknown parameter '{}'", key))),
+        }
+    }
+
+    let mut options = options_from_flags(&flags, config).map_err(|e| Response::error(400, e))?;
+    if options.width > limits.max_width {
+        return Err(Response::error(400, format!("Width {} exceeds the server limit of {}", options.width, limits.max_width)));
+    }
+
+    // SVG rasters are bounded by the rasterizer; HEIF and AVIF declare their
+    // size in the container, everything else in a header `image` can read.
+    let size = match input::sniff(image) {
+        Some(Container::Svg) => None,
+        Some(Container::Heif | Container::Avif) => Some(input::heif_dimensions(image)),
+        None => Some(
+            image::io::Reader::new(Cursor::new(image))
+                .with_guessed_format()
+                .ok()
+                .and_then(|reader| reader.into_dimensions().ok()),
+        ),
+    };
+    if let Some(size) = size {
+        let (width, height) = size.ok_or_else(|| Response::error(422, "Could not read the uploaded image"))?;
+        if width as u64 * height as u64 > MAX_PIXELS {
+            return Err(Response::error(413, format!("Image is {}x{}; the limit is {} pixels", width, height, MAX_PIXELS)));
+        }
+    }
+    let img = decode_image(image, None, &options).map_err(|e| Response::error(422, format!("Could not decode image: {}", e)))?;
+    options.resolve_image_size(&img);
+
+    let ascii_data = convert_image(&img, &options).map_err(|e| Response::error(400, e.to_string()))?;
+    if ascii_data.first().is_none_or(|row| row.is_empty()) {
+        return Err(Response::error(422, "Image is too small to convert at this width"));
+    }
+    if format == OutputFormat::Png {
+        let (width, height) = rendered_size(&ascii_data, &options);
+        if width as u64 * height as u64 > MAX_PIXELS {
+            return Err(Response::error(413, format!("Rendered image would be {}x{}; the limit is {} pixels", width, height, MAX_PIXELS)));
+        }
+    }
+    let body = encode_grid(format, ascii_data, "-", &options, tolerance, &raster).map_err(|e| Response::error(500, e.to_string()))?;
+    let content_type = match format {
+        OutputFormat::Png => raster.format.mime_type(),
+        _ => format.content_type(),
+    };
+    Ok(Response::ok(content_type, body))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limits() -> Limits {
+        Limits { max_upload: 64, timeout: Duration::from_secs(5), max_width: 100, max_connections: 1 }
+    }
+
+    /// Runs `read_request` on a connection that receives `bytes`.
+    fn receive(bytes: &[u8], limits: &Limits) -> Result<Request, Response> {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let bytes = bytes.to_vec();
+        let client = thread::spawn(move || {
+            let mut stream = TcpStream::connect(address).unwrap();
+            let _ = stream.write_all(&bytes);
+            // Held open until the server side is done.
+            let _ = stream.read(&mut [0; 64]);
+        });
+        let (mut stream, _) = listener.accept().unwrap();
+        let result = read_request(&mut stream, Instant::now() + limits.timeout, limits);
+        drop(stream);
+        client.join().unwrap();
+        result
+    }
+
+    fn status(result: Result<Request, Response>) -> u16 {
+        result.err().map_or(200, |response| response.status)
+    }
+
+    #[test]
+    fn reads_head_and_body() {
+        let request = receive(b"POST /convert?format=text HTTP/1.1\r\nContent-Length: 5\r\nX-Test: a:b\r\n\r\nhello", &limits()).unwrap();
+        assert_eq!((request.method.as_str(), request.path.as_str(), request.query.as_str()), ("POST", "/convert", "format=text"));
+        assert_eq!(request.header("x-test"), Some("a:b"));
+        assert_eq!(request.body, b"hello");
+    }
+
+    #[test]
+    fn enforces_request_limits() {
+        assert_eq!(status(receive(b"POST / HTTP/1.1\r\nContent-Length: 65\r\n\r\n", &limits())), 413);
+        assert_eq!(status(receive(b"POST / HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n", &limits())), 411);
+        assert_eq!(status(receive(b"POST / HTTP/1.1\r\nContent-Length: x\r\n\r\n", &limits())), 400);
+        let huge = format!("GET / HTTP/1.1\r\nX: {}\r\n\r\n", "a".repeat(MAX_HEADER_BYTES));
+        assert_eq!(status(receive(huge.as_bytes(), &limits())), 431);
+    }
+
+    #[test]
+    fn slow_requests_time_out() {
+        let limits = Limits { timeout: Duration::from_millis(100), ..limits() };
+        assert_eq!(status(receive(b"POST / HTTP/1.1\r\nContent-Length: 10\r\n\r\nabc", &limits)), 408);
+    }
+
+    #[test]
+    fn percent_decoding() {
+        assert_eq!(percent_decode("a%20b+c%2Cd"), "a b c,d");
+        assert_eq!(percent_decode("%23000%2c%23fff"), "#000,#fff");
+        assert_eq!(percent_decode("100%"), "100%");
+        assert_eq!(percent_decode("%zz%4"), "%zz%4");
+    }
+
+    #[test]
+    fn multipart_fields_and_file() {
+        let body = b"preamble\r\n--XyZ\r\nContent-Disposition: form-data; name=\"density\"\r\n\r\nhigh\r\n--XyZ\r\nContent-Disposition: form-data; name=\"image\"; filename=\"a.png\"\r\nContent-Type: image/png\r\n\r\n\x89PNG\r\n--XyZ--\r\n";
+        let multipart = parse_multipart(body, "XyZ").ok().unwrap();
+        assert_eq!(multipart.fields, [("density".to_string(), "high".to_string())]);
+        assert_eq!(multipart.file, Some(&b"\x89PNG"[..]));
+    }
+
+    #[test]
+    fn malformed_multipart_bodies_are_rejected() {
+        assert_eq!(parse_multipart(b"no parts here", "XyZ").err().map(|r| r.status), Some(400));
+        assert_eq!(parse_multipart(b"--XyZ\r\nContent-Disposition: form-data; name=\"a\"\r\n\r\n1", "XyZ").err().map(|r| r.status), Some(400));
+    }
+
+    #[test]
+    fn switches_accept_common_spellings() {
+        assert!(parse_switch("invert", "").unwrap() && parse_switch("invert", "On").unwrap());
+        assert!(!parse_switch("invert", "0").unwrap());
+        assert_eq!(parse_switch("invert", "maybe").err().map(|r| r.status), Some(400));
+    }
+
+    fn upload(body: Vec<u8>) -> Result<Response, Response> {
+        let request = Request { method: "POST".to_string(), path: "/convert".to_string(), query: String::new(), headers: Vec::new(), body };
+        convert(&request, &limits(), &Config::default())
+    }
+
+    #[test]
+    fn heif_uploads_are_size_checked_before_decoding() {
+        let ftyp = |brand: &[u8; 4]| [&[0, 0, 0, 16][..], b"ftyp", brand, &[0, 0, 0, 0]].concat();
+        let mut huge = ftyp(b"heic");
+        huge.extend([&[0, 0, 0, 20][..], b"ispe", &[0; 4], &20000u32.to_be_bytes(), &20000u32.to_be_bytes()].concat());
+        assert_eq!(upload(huge).err().map(|r| r.status), Some(413));
+        assert_eq!(upload(ftyp(b"avif")).err().map(|r| r.status), Some(422));
+    }
+}
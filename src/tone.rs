@@ -0,0 +1,253 @@
+#[derive(Clone, Debug, PartialEq)]
+pub struct Adjustments {
+    pub brightness: f32,
+    pub contrast: f32,
+    pub gamma: f32,
+    pub auto_levels: bool,
+    pub equalize: bool,
+}
+
+impl Default for Adjustments {
+    fn default() -> Self {
+        Adjustments {
+            brightness: 0.0,
+            contrast: 1.0,
+            gamma: 1.0,
+            auto_levels: false,
+            equalize: false,
+        }
+    }
+}
+
+impl Adjustments {
+    /// Remaps normalized (0.0..=1.0) brightness values in place. Histogram
+    /// operations run first so that brightness, contrast and gamma act on
+    /// the stretched range.
+    pub fn apply(&self, values: &mut [f32]) {
+        if self.equalize {
+            equalize(values);
+        } else if self.auto_levels {
+            stretch_levels(values);
+        }
+
+        for value in values.iter_mut() {
+            let mut v = (*value - 0.5) * self.contrast + 0.5 + self.brightness;
+            v = v.clamp(0.0, 1.0);
+            if self.gamma != 1.0 {
+                v = v.powf(1.0 / self.gamma);
+            }
+            *value = v;
+        }
+    }
+}
+
+fn stretch_levels(values: &mut [f32]) {
+    let (min, max) = values
+        .iter()
+        .fold((f32::MAX, f32::MIN), |(lo, hi), &v| (lo.min(v), hi.max(v)));
+    let range = max - min;
+    if range <= f32::EPSILON {
+        return;
+    }
+    for value in values.iter_mut() {
+        *value = (*value - min) / range;
+    }
+}
+
+fn equalize(values: &mut [f32]) {
+    const BINS: usize = 256;
+    if values.is_empty() {
+        return;
+    }
+
+    let bin_of = |v: f32| ((v.clamp(0.0, 1.0) * (BINS - 1) as f32).round()) as usize;
+
+    let mut histogram = [0usize; BINS];
+    for &v in values.iter() {
+        histogram[bin_of(v)] += 1;
+    }
+
+    let mut cdf = [0usize; BINS];
+    let mut total = 0;
+    for (bin, &count) in histogram.iter().enumerate() {
+        total += count;
+        cdf[bin] = total;
+    }
+
+    let cdf_min = cdf.iter().copied().find(|&c| c > 0).unwrap_or(0);
+    let denominator = (values.len() - cdf_min) as f32;
+    if denominator <= 0.0 {
+        return;
+    }
+
+    for value in values.iter_mut() {
+        *value = (cdf[bin_of(*value)] - cdf_min) as f32 / denominator;
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Luminance {
+    Rec601,
+    Rec709,
+    Lstar,
+}
+
+pub fn parse_luminance(s: &str) -> Result<Luminance, String> {
+    match s.to_lowercase().as_str() {
+        "rec601" | "601" => Ok(Luminance::Rec601),
+        "rec709" | "709" => Ok(Luminance::Rec709),
+        "lstar" | "l*" | "cie" => Ok(Luminance::Lstar),
+        _ => Err(format!("Invalid luminance '{}'. Use: rec601, rec709, lstar", s)),
+    }
+}
+
+impl Luminance {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Luminance::Rec601 => "rec601",
+            Luminance::Rec709 => "rec709",
+            Luminance::Lstar => "lstar",
+        }
+    }
+
+    pub fn next(&self) -> Luminance {
+        match self {
+            Luminance::Lstar => Luminance::Rec709,
+            Luminance::Rec709 => Luminance::Rec601,
+            Luminance::Rec601 => Luminance::Lstar,
+        }
+    }
+
+    /// Normalized (0.0..=1.0) lightness of an sRGB color.
+    ///
+    /// `Rec601` is the legacy luma of the gamma-encoded values, `Rec709` is
+    /// linear relative luminance and `Lstar` is CIE L*, which is perceptually
+    /// uniform and therefore spreads evenly over density-ordered ramps.
+    pub fn level(&self, r: u8, g: u8, b: u8) -> f32 {
+        match self {
+            Luminance::Rec601 => (r as f32 * 0.299 + g as f32 * 0.587 + b as f32 * 0.114) / 255.0,
+            Luminance::Rec709 => relative_luminance(r, g, b),
+            Luminance::Lstar => lightness(relative_luminance(r, g, b)),
+        }
+    }
+}
+
+fn srgb_to_linear(channel: u8) -> f32 {
+    let c = channel as f32 / 255.0;
+    if c <= 0.04045 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+fn relative_luminance(r: u8, g: u8, b: u8) -> f32 {
+    0.2126 * srgb_to_linear(r) + 0.7152 * srgb_to_linear(g) + 0.0722 * srgb_to_linear(b)
+}
+
+fn lightness(y: f32) -> f32 {
+    const EPSILON: f32 = 216.0 / 24389.0;
+    const KAPPA: f32 = 24389.0 / 27.0;
+    let l = if y <= EPSILON {
+        y * KAPPA
+    } else {
+        116.0 * y.cbrt() - 16.0
+    };
+    (l / 100.0).clamp(0.0, 1.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: f32, b: f32) -> bool {
+        (a - b).abs() < 1e-5
+    }
+
+    #[test]
+    fn defaults_leave_values_unchanged() {
+        let mut values = [0.0, 0.25, 0.5, 1.0];
+        Adjustments::default().apply(&mut values);
+        assert_eq!(values, [0.0, 0.25, 0.5, 1.0]);
+    }
+
+    #[test]
+    fn histogram_operations_leave_flat_images_alone() {
+        for adjustments in [
+            Adjustments { auto_levels: true, ..Adjustments::default() },
+            Adjustments { equalize: true, ..Adjustments::default() },
+        ] {
+            let mut values = [0.4; 6];
+            adjustments.apply(&mut values);
+            assert_eq!(values, [0.4; 6]);
+        }
+    }
+
+    #[test]
+    fn stretch_levels_spans_the_full_range() {
+        let mut values = [0.2, 0.3, 0.6];
+        stretch_levels(&mut values);
+        assert!(close(values[0], 0.0) && close(values[1], 0.25) && close(values[2], 1.0), "{:?}", values);
+    }
+
+    #[test]
+    fn equalize_covers_zero_to_one() {
+        let mut values = [0.1, 0.1, 0.12, 0.13, 0.14, 0.9];
+        equalize(&mut values);
+        assert_eq!(values[0], 0.0);
+        assert_eq!(values[5], 1.0);
+        assert!(values.windows(2).all(|pair| pair[0] <= pair[1]), "{:?}", values);
+    }
+
+    #[test]
+    fn contrast_pivots_around_mid_gray() {
+        let mut values = [0.25, 0.5, 0.75];
+        Adjustments { contrast: 2.0, ..Adjustments::default() }.apply(&mut values);
+        assert_eq!(values, [0.0, 0.5, 1.0]);
+
+        let mut values = [0.25, 0.5];
+        Adjustments { contrast: -1.0, ..Adjustments::default() }.apply(&mut values);
+        assert_eq!(values, [0.75, 0.5]);
+    }
+
+    #[test]
+    fn brightness_clamps_and_gamma_lifts_shadows() {
+        let mut values = [0.8, 0.25];
+        Adjustments { brightness: 0.5, ..Adjustments::default() }.apply(&mut values);
+        assert_eq!(values, [1.0, 0.75]);
+
+        let mut values = [0.25];
+        Adjustments { gamma: 2.0, ..Adjustments::default() }.apply(&mut values);
+        assert!(close(values[0], 0.5));
+    }
+
+    #[test]
+    fn luminance_models_agree_on_black_and_white() {
+        for model in [Luminance::Rec601, Luminance::Rec709, Luminance::Lstar] {
+            assert!(close(model.level(0, 0, 0), 0.0), "{:?}", model);
+            assert!(close(model.level(255, 255, 255), 1.0), "{:?}", model);
+        }
+    }
+
+    #[test]
+    fn lstar_puts_middle_gray_near_the_middle() {
+        // sRGB 119 is about 18% reflectance, which CIE L* places near 50.
+        assert!((Luminance::Lstar.level(119, 119, 119) - 0.5).abs() < 0.01);
+        assert!(Luminance::Rec709.level(119, 119, 119) < 0.2);
+    }
+
+    #[test]
+    fn green_outweighs_blue() {
+        for model in [Luminance::Rec601, Luminance::Rec709, Luminance::Lstar] {
+            assert!(model.level(0, 255, 0) > model.level(0, 0, 255), "{:?}", model);
+        }
+    }
+
+    #[test]
+    fn luminance_names_round_trip() {
+        for model in [Luminance::Rec601, Luminance::Rec709, Luminance::Lstar] {
+            assert_eq!(parse_luminance(model.name()), Ok(model));
+        }
+        assert!(parse_luminance("hsv").is_err());
+    }
+}
@@ -0,0 +1,193 @@
+use crate::play::{TerminalGuard, draw_frame};
+use crate::shell_quote;
+use bitify::{AsciiGrid, ConvertOptions, convert_image, load_image, save_ascii_png};
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use crossterm::style::{Print, ResetColor};
+use crossterm::{cursor, queue, terminal};
+use std::fs;
+use std::io::{self, IsTerminal, Write};
+use std::path::Path;
+
+const WIDTH_STEP: u32 = 4;
+const MIN_WIDTH: u32 = 8;
+const STATUS_LINES: usize = 2;
+
+const HELP: &str = "d:density  [/]:width  g/G:gamma  c/C:contrast  b/B:brightness  l:luminance  a:levels  m:color  i:invert  s:save  q:quit";
+
+/// Applies one keypress to the options. Returns `false` for keys that do not
+/// change the conversion.
+fn adjust(options: &mut ConvertOptions, code: KeyCode) -> bool {
+    let adjustments = &mut options.adjustments;
+    match code {
+        KeyCode::Char('d') => {
+            options.density = options.density.next();
+            options.charset = None;
+        }
+        KeyCode::Char(']') | KeyCode::Right => options.width += WIDTH_STEP,
+        KeyCode::Char('[') | KeyCode::Left => options.width = options.width.saturating_sub(WIDTH_STEP).max(MIN_WIDTH),
+        KeyCode::Char('G') => adjustments.gamma = (adjustments.gamma + 0.1).min(5.0),
+        KeyCode::Char('g') => adjustments.gamma = (adjustments.gamma - 0.1).max(0.1),
+        KeyCode::Char('C') => adjustments.contrast = (adjustments.contrast + 0.1).min(5.0),
+        KeyCode::Char('c') => adjustments.contrast = (adjustments.contrast - 0.1).max(0.0),
+        KeyCode::Char('B') => adjustments.brightness = (adjustments.brightness + 0.05).min(1.0),
+        KeyCode::Char('b') => adjustments.brightness = (adjustments.brightness - 0.05).max(-1.0),
+        KeyCode::Char('l') => options.luminance = options.luminance.next(),
+        KeyCode::Char('a') => {
+            // off -> auto-levels -> equalize -> off
+            (adjustments.auto_levels, adjustments.equalize) = match (adjustments.auto_levels, adjustments.equalize) {
+                (false, false) => (true, false),
+                (true, _) => (false, true),
+                (false, true) => (false, false),
+            };
+        }
+        KeyCode::Char('m') => options.color = options.color.next(),
+        KeyCode::Char('i') => options.invert = !options.invert,
+        _ => return false,
+    }
+    true
+}
+
+fn describe(options: &ConvertOptions) -> String {
+    let levels = if options.adjustments.equalize {
+        "equalize"
+    } else if options.adjustments.auto_levels {
+        "auto"
+    } else {
+        "off"
+    };
+    format!(
+        "density {}  width {}  gamma {:.1}  contrast {:.1}  brightness {:+.2}  luminance {}  levels {}  color {}{}",
+        options.density.name(),
+        options.width,
+        options.adjustments.gamma,
+        options.adjustments.contrast,
+        options.adjustments.brightness,
+        options.luminance.name(),
+        levels,
+        options.color.name(),
+        if options.invert { "  inverted" } else { "" },
+    )
+}
+
+/// Saves the full-width PNG to the output directory and writes the
+/// equivalent command line next to it so the result can be regenerated
+/// outside the TUI.
+fn save(image_path: &str, output_dir: &Path, img: &image::DynamicImage, options: &ConvertOptions) -> Result<String, Box<dyn std::error::Error>> {
+    let grid = convert_image(img, options)?;
+    let png_path = save_ascii_png(&grid, image_path, output_dir, options)?;
+
+    let mut command = vec!["bitify".to_string()];
+    command.extend(options.to_args().iter().map(|arg| shell_quote(arg)));
+    command.push(shell_quote(image_path));
+    let settings_path = png_path.with_extension("settings");
+    fs::write(&settings_path, format!("{}\n", command.join(" ")))?;
+
+    Ok(format!("Saved {} ({})", png_path.display(), command.join(" ")))
+}
+
+fn draw_status(out: &mut impl Write, row: usize, columns: usize, options: &ConvertOptions, message: &str) -> io::Result<()> {
+    let truncate = |text: &str| text.chars().take(columns).collect::<String>();
+    let first = if message.is_empty() { describe(options) } else { message.to_string() };
+    queue!(
+        out,
+        ResetColor,
+        cursor::MoveTo(0, row as u16),
+        terminal::Clear(terminal::ClearType::FromCursorDown),
+        Print(truncate(&first)),
+        cursor::MoveTo(0, row as u16 + 1),
+        Print(truncate(HELP)),
+    )
+}
+
+pub fn run(image_path: &str, output_dir: &Path, mut options: ConvertOptions) -> Result<(), Box<dyn std::error::Error>> {
+    if !io::stdout().is_terminal() {
+        return Err("tui needs an interactive terminal".into());
+    }
+    let img = load_image(image_path, &options)?;
+    options.resolve_image_size(&img);
+
+    let _guard = TerminalGuard::enter()?;
+    let mut stdout = io::stdout().lock();
+
+    let mut previous: Option<AsciiGrid> = None;
+    let mut message = String::new();
+    let mut dirty = true;
+
+    loop {
+        let (columns, rows) = terminal::size()?;
+        let max_rows = (rows as usize).saturating_sub(STATUS_LINES);
+
+        if dirty {
+            let mut preview_options = options.clone();
+            preview_options.width = options.width.min(columns as u32);
+            let grid = convert_image(&img, &preview_options)?;
+            draw_frame(&mut stdout, &grid, previous.as_ref(), max_rows, options.color)?;
+            draw_status(&mut stdout, grid.len().min(max_rows), columns as usize, &options, &message)?;
+            stdout.flush()?;
+            previous = Some(grid);
+            dirty = false;
+        }
+
+        match event::read()? {
+            Event::Key(KeyEvent { code, modifiers, kind, .. }) if kind != KeyEventKind::Release => {
+                message.clear();
+                match code {
+                    KeyCode::Char('c') if modifiers.contains(KeyModifiers::CONTROL) => break,
+                    KeyCode::Char('q') | KeyCode::Esc => break,
+                    KeyCode::Char('s') => {
+                        message = match save(image_path, output_dir, &img, &options) {
+                            Ok(saved) => saved,
+                            Err(e) => format!("Save failed: {}", e),
+                        };
+                        dirty = true;
+                    }
+                    code => dirty = adjust(&mut options, code),
+                }
+            }
+            Event::Resize(_, _) => {
+                previous = None;
+                dirty = true;
+            }
+            _ => {}
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bitify::DensityPreset;
+
+    #[test]
+    fn keys_stay_within_their_ranges() {
+        let mut options = ConvertOptions::new(DensityPreset::Medium);
+        options.width = 10;
+        for _ in 0..5 {
+            adjust(&mut options, KeyCode::Left);
+        }
+        assert_eq!(options.width, MIN_WIDTH);
+
+        for _ in 0..100 {
+            adjust(&mut options, KeyCode::Char('G'));
+            adjust(&mut options, KeyCode::Char('b'));
+            adjust(&mut options, KeyCode::Char('c'));
+        }
+        assert_eq!(options.adjustments.gamma, 5.0);
+        assert_eq!(options.adjustments.brightness, -1.0);
+        assert_eq!(options.adjustments.contrast, 0.0);
+    }
+
+    #[test]
+    fn levels_key_cycles_through_the_histogram_modes() {
+        let mut options = ConvertOptions::new(DensityPreset::Medium);
+        let mut seen = Vec::new();
+        for _ in 0..3 {
+            adjust(&mut options, KeyCode::Char('a'));
+            seen.push((options.adjustments.auto_levels, options.adjustments.equalize));
+        }
+        assert_eq!(seen, [(true, false), (false, true), (false, false)]);
+        assert!(!adjust(&mut options, KeyCode::Char('z')));
+    }
+}
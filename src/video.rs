@@ -0,0 +1,356 @@
+use bitify::{ConvertOptions, convert_image, render_ascii_png, transform_image};
+use image::{DynamicImage, RgbImage};
+use std::fs;
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Child, ChildStdin, ChildStdout, Command, Stdio};
+
+const DEFAULT_SEQUENCE_FPS: FrameRate = FrameRate { num: 24, den: 1 };
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FrameRate {
+    pub num: u32,
+    pub den: u32,
+}
+
+/// Accepts `24`, `29.97` or a rational such as `30000/1001`, the form ffprobe
+/// reports.
+pub fn parse_frame_rate(s: &str) -> Result<FrameRate, String> {
+    let invalid = || format!("Invalid frame rate '{}'", s);
+    let rate = if let Some((num, den)) = s.split_once('/') {
+        FrameRate {
+            num: num.trim().parse().map_err(|_| invalid())?,
+            den: den.trim().parse().map_err(|_| invalid())?,
+        }
+    } else {
+        let fps: f64 = s.trim().parse().map_err(|_| invalid())?;
+        FrameRate {
+            num: (fps * 1000.0).round() as u32,
+            den: 1000,
+        }
+    };
+    if rate.num == 0 || rate.den == 0 {
+        return Err(invalid());
+    }
+    Ok(rate)
+}
+
+impl FrameRate {
+    pub fn as_f64(&self) -> f64 {
+        self.num as f64 / self.den as f64
+    }
+
+    fn to_ffmpeg(self) -> String {
+        format!("{}/{}", self.num, self.den)
+    }
+}
+
+pub enum FrameSource {
+    Directory(std::vec::IntoIter<PathBuf>),
+    Ffmpeg {
+        child: Child,
+        stdout: BufReader<ChildStdout>,
+    },
+}
+
+impl FrameSource {
+    /// Opens `input` as a directory of image frames (sorted by file name) or,
+    /// for anything else, as a video decoded by a local `ffmpeg`.
+    pub fn open(input: &str) -> Result<FrameSource, Box<dyn std::error::Error>> {
+        if Path::new(input).is_dir() {
+            let mut frames: Vec<PathBuf> = fs::read_dir(input)?
+                .filter_map(|entry| entry.ok().map(|e| e.path()))
+                .filter(|path| path.is_file() && image::ImageFormat::from_path(path).is_ok())
+                .collect();
+            if frames.is_empty() {
+                return Err(format!("No image frames found in '{}'", input).into());
+            }
+            frames.sort();
+            return Ok(FrameSource::Directory(frames.into_iter()));
+        }
+
+        let mut child = Command::new("ffmpeg")
+            .args(["-v", "error", "-nostdin", "-i", input])
+            .args(["-f", "image2pipe", "-c:v", "ppm", "-pix_fmt", "rgb24", "-"])
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .spawn()
+            .map_err(|e| format!("Could not run ffmpeg ({}); is it installed?", e))?;
+        let stdout = BufReader::new(child.stdout.take().ok_or("ffmpeg stdout unavailable")?);
+        Ok(FrameSource::Ffmpeg { child, stdout })
+    }
+
+    pub fn next_frame(&mut self) -> Result<Option<DynamicImage>, Box<dyn std::error::Error>> {
+        match self {
+            FrameSource::Directory(frames) => match frames.next() {
+                Some(path) => Ok(Some(image::open(path)?)),
+                None => Ok(None),
+            },
+            FrameSource::Ffmpeg { child, stdout } => match read_ppm_frame(stdout)? {
+                Some(frame) => Ok(Some(DynamicImage::ImageRgb8(frame))),
+                None => {
+                    let status = child.wait()?;
+                    if !status.success() {
+                        return Err(format!("ffmpeg exited with {}", status).into());
+                    }
+                    Ok(None)
+                }
+            },
+        }
+    }
+}
+
+impl Drop for FrameSource {
+    fn drop(&mut self) {
+        if let FrameSource::Ffmpeg { child, .. } = self {
+            let _ = child.kill();
+            let _ = child.wait();
+        }
+    }
+}
+
+/// Reads one binary PPM (`P6`) image from an `image2pipe` stream. A clean end
+/// of stream before the header returns `None`.
+pub fn read_ppm_frame(reader: &mut impl BufRead) -> Result<Option<RgbImage>, Box<dyn std::error::Error>> {
+    if reader.fill_buf()?.is_empty() {
+        return Ok(None);
+    }
+
+    let mut fields = Vec::with_capacity(4);
+    while fields.len() < 4 {
+        let mut token = Vec::new();
+        loop {
+            let mut byte = [0u8; 1];
+            reader.read_exact(&mut byte)?;
+            if byte[0] == b'#' {
+                let mut comment = Vec::new();
+                reader.read_until(b'\n', &mut comment)?;
+                continue;
+            }
+            if byte[0].is_ascii_whitespace() {
+                if token.is_empty() {
+                    continue;
+                }
+                break;
+            }
+            token.push(byte[0]);
+        }
+        fields.push(String::from_utf8(token)?);
+    }
+
+    if fields[0] != "P6" || fields[3] != "255" {
+        return Err(format!("Unexpected frame header from ffmpeg: {:?}", fields).into());
+    }
+    let width: u32 = fields[1].parse()?;
+    let height: u32 = fields[2].parse()?;
+
+    let mut pixels = vec![0u8; width as usize * height as usize * 3];
+    reader.read_exact(&mut pixels)?;
+    Ok(RgbImage::from_raw(width, height, pixels))
+}
+
+/// Asks `ffprobe` for the average frame rate of the first video stream.
+pub fn probe_frame_rate(input: &str) -> Result<FrameRate, Box<dyn std::error::Error>> {
+    let output = Command::new("ffprobe")
+        .args(["-v", "error", "-select_streams", "v:0"])
+        .args(["-show_entries", "stream=avg_frame_rate,r_frame_rate", "-of", "default=nw=1"])
+        .arg(input)
+        .output()
+        .map_err(|e| format!("Could not run ffprobe ({}); is it installed?", e))?;
+    if !output.status.success() {
+        return Err(format!("ffprobe failed: {}", String::from_utf8_lossy(&output.stderr).trim()).into());
+    }
+
+    let report = String::from_utf8_lossy(&output.stdout);
+    let rate_of = |key: &str| {
+        report
+            .lines()
+            .find_map(|line| line.strip_prefix(key))
+            .and_then(|value| parse_frame_rate(value).ok())
+    };
+    rate_of("avg_frame_rate=")
+        .or_else(|| rate_of("r_frame_rate="))
+        .ok_or_else(|| format!("Could not determine the frame rate of '{}'", input).into())
+}
+
+enum FrameSink {
+    Sequence(PathBuf),
+    Ffmpeg {
+        child: Child,
+        stdin: ChildStdin,
+        size: (u32, u32),
+    },
+}
+
+impl FrameSink {
+    fn write(&mut self, index: usize, frame: &RgbImage) -> Result<(), Box<dyn std::error::Error>> {
+        match self {
+            FrameSink::Sequence(dir) => {
+                frame.save(dir.join(format!("frame_{:06}.png", index + 1)))?;
+            }
+            FrameSink::Ffmpeg { stdin, size, .. } => {
+                if frame.dimensions() != *size {
+                    return Err("Frame size changed mid-video; ASCII frames must all match".into());
+                }
+                stdin.write_all(frame.as_raw())?;
+            }
+        }
+        Ok(())
+    }
+
+    fn finish(self) -> Result<(), Box<dyn std::error::Error>> {
+        if let FrameSink::Ffmpeg { mut child, stdin, .. } = self {
+            drop(stdin);
+            let status = child.wait()?;
+            if !status.success() {
+                return Err(format!("ffmpeg encoder exited with {}", status).into());
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Starts an ffmpeg encoder fed with raw RGB frames. When the source is a
+/// video its audio track, if any, is carried over.
+fn spawn_encoder(
+    output: &str,
+    size: (u32, u32),
+    rate: FrameRate,
+    audio_source: Option<&str>,
+) -> Result<FrameSink, Box<dyn std::error::Error>> {
+    let mut command = Command::new("ffmpeg");
+    command
+        .args(["-v", "error", "-y"])
+        .args(["-f", "rawvideo", "-pix_fmt", "rgb24"])
+        .args(["-s", &format!("{}x{}", size.0, size.1)])
+        .args(["-framerate", &rate.to_ffmpeg()])
+        .args(["-i", "-"]);
+    if let Some(source) = audio_source {
+        command
+            .args(["-i", source])
+            .args(["-map", "0:v:0", "-map", "1:a:0?", "-shortest"]);
+    }
+    command
+        .args(["-vf", "pad=ceil(iw/2)*2:ceil(ih/2)*2"])
+        .args(["-pix_fmt", "yuv420p", "-r", &rate.to_ffmpeg()])
+        .arg(output)
+        .stdin(Stdio::piped());
+
+    let mut child = command
+        .spawn()
+        .map_err(|e| format!("Could not run ffmpeg ({}); is it installed?", e))?;
+    let stdin = child.stdin.take().ok_or("ffmpeg stdin unavailable")?;
+    Ok(FrameSink::Ffmpeg { child, stdin, size })
+}
+
+fn is_sequence_output(output: &str) -> bool {
+    let path = Path::new(output);
+    path.is_dir() || path.extension().is_none()
+}
+
+/// Runs every frame of `input` through the image pipeline and writes the
+/// rendered ASCII frames as a video (kept at the source frame rate) or as a
+/// numbered PNG sequence when `output` is a directory.
+pub fn convert_video(
+    input: &str,
+    output: &str,
+    fps: Option<FrameRate>,
+    options: &ConvertOptions,
+) -> Result<(), Box<dyn std::error::Error>> {
+    if options.trim.is_some() {
+        return Err("--trim is not supported for video; use --crop so every frame keeps the same size".into());
+    }
+
+    let from_directory = Path::new(input).is_dir();
+    let rate = match fps {
+        Some(rate) => rate,
+        None if from_directory => DEFAULT_SEQUENCE_FPS,
+        None => probe_frame_rate(input)?,
+    };
+
+    let mut source = FrameSource::open(input)?;
+    let mut sink = if is_sequence_output(output) {
+        fs::create_dir_all(output)?;
+        Some(FrameSink::Sequence(PathBuf::from(output)))
+    } else {
+        None
+    };
+
+    let mut count = 0;
+    let mut resolved = None;
+    while let Some(frame) = source.next_frame()? {
+        let frame = transform_image(frame, options)?;
+        // Frames share one size, so the first one resolves `--match-source`.
+        let options = resolved.get_or_insert_with(|| {
+            let mut options = options.clone();
+            options.resolve_image_size(&frame);
+            options
+        });
+        let ascii_data = convert_image(&frame, options)?;
+        let rendered = render_ascii_png(&ascii_data, options);
+
+        if sink.is_none() {
+            let audio_source = (!from_directory).then_some(input);
+            sink = Some(spawn_encoder(output, rendered.dimensions(), rate, audio_source)?);
+        }
+        if let Some(sink) = sink.as_mut() {
+            sink.write(count, &rendered)?;
+        }
+
+        count += 1;
+        eprint!("\rConverted frame {}", count);
+    }
+    eprintln!();
+
+    if count == 0 {
+        return Err(format!("No frames decoded from '{}'", input).into());
+    }
+    if let Some(sink) = sink {
+        sink.finish()?;
+    }
+    println!("✨ {} frames at {:.3} fps written to {}", count, rate.as_f64(), output);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn frame_rates_parse_as_rationals() {
+        assert_eq!(parse_frame_rate("24"), Ok(FrameRate { num: 24000, den: 1000 }));
+        assert_eq!(parse_frame_rate("30000/1001"), Ok(FrameRate { num: 30000, den: 1001 }));
+        assert!((parse_frame_rate("29.97").unwrap().as_f64() - 29.97).abs() < 1e-9);
+        assert_eq!(parse_frame_rate("25/1").unwrap().to_ffmpeg(), "25/1");
+        for bad in ["0", "0/1", "1/0", "-5", "fast", ""] {
+            assert!(parse_frame_rate(bad).is_err(), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn ppm_frames_are_read_back_to_back() {
+        let mut stream = b"P6\n# from ffmpeg\n2 1\n255\n\x01\x02\x03\x04\x05\x06".to_vec();
+        stream.extend(b"P6 1 1 255\n\xff\x00\x00");
+        let mut reader = Cursor::new(stream);
+
+        let first = read_ppm_frame(&mut reader).unwrap().unwrap();
+        assert_eq!(first.dimensions(), (2, 1));
+        assert_eq!(first.as_raw(), &[1, 2, 3, 4, 5, 6]);
+        let second = read_ppm_frame(&mut reader).unwrap().unwrap();
+        assert_eq!(second.as_raw(), &[255, 0, 0]);
+        assert!(read_ppm_frame(&mut reader).unwrap().is_none());
+    }
+
+    #[test]
+    fn bad_ppm_frames_are_errors() {
+        assert!(read_ppm_frame(&mut Cursor::new(b"P5\n1 1\n255\n\0".to_vec())).is_err());
+        assert!(read_ppm_frame(&mut Cursor::new(b"P6\n2 2\n255\n\0\0\0".to_vec())).is_err());
+    }
+
+    #[test]
+    fn outputs_without_an_extension_are_sequences() {
+        assert!(is_sequence_output("frames"));
+        assert!(!is_sequence_output("clip.mp4"));
+    }
+}
@@ -0,0 +1,85 @@
+//! Browser bindings, built with
+//! `wasm-pack build --target web -- --no-default-features --features png-render,jpeg,png`:
+//!
+//! ```js
+//! const options = new Options();
+//! options.setDensity("high");
+//! const png = toPng(new Uint8Array(await file.arrayBuffer()), options);
+//! ```
+
+use crate::palette::parse_color_mode;
+use crate::{ConvertOptions, DensityPreset, convert_bytes, html, parse_charset, parse_density};
+use wasm_bindgen::prelude::*;
+
+/// Conversion settings; anything not set keeps the command's default.
+#[wasm_bindgen]
+pub struct Options {
+    options: ConvertOptions,
+    /// Explicit width, kept when the density (and its default width) changes.
+    width: Option<u32>,
+}
+
+#[wasm_bindgen]
+impl Options {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> Options {
+        Options { options: ConvertOptions::new(DensityPreset::Medium), width: None }
+    }
+
+    #[wasm_bindgen(js_name = setDensity)]
+    pub fn set_density(&mut self, density: &str) -> Result<(), JsError> {
+        let density = parse_density(density).map_err(|e| JsError::new(&e))?;
+        self.options.width = self.width.unwrap_or_else(|| density.get_default_width());
+        self.options.density = density;
+        Ok(())
+    }
+
+    #[wasm_bindgen(js_name = setWidth)]
+    pub fn set_width(&mut self, width: u32) {
+        self.width = Some(width);
+        self.options.width = width;
+    }
+
+    #[wasm_bindgen(js_name = setCharset)]
+    pub fn set_charset(&mut self, charset: &str) -> Result<(), JsError> {
+        self.options.charset = Some(parse_charset(charset).map_err(|e| JsError::new(&e))?);
+        Ok(())
+    }
+
+    #[wasm_bindgen(js_name = setColor)]
+    pub fn set_color(&mut self, color: &str) -> Result<(), JsError> {
+        self.options.color = parse_color_mode(color).map_err(|e| JsError::new(&e))?;
+        Ok(())
+    }
+
+    #[wasm_bindgen(js_name = setInvert)]
+    pub fn set_invert(&mut self, invert: bool) {
+        self.options.invert = invert;
+    }
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options::new()
+    }
+}
+
+fn convert(bytes: &[u8], options: &ConvertOptions) -> Result<crate::AsciiGrid, JsError> {
+    convert_bytes(bytes, options).map_err(|e| JsError::new(&e.to_string()))
+}
+
+/// Converts encoded image bytes to the ASCII art PNG, with settings embedded
+/// as the command would write them.
+#[cfg(feature = "png-render")]
+#[wasm_bindgen(js_name = toPng)]
+pub fn to_png(bytes: &[u8], options: &Options) -> Result<Vec<u8>, JsError> {
+    let options = &options.options;
+    let ascii_data = convert(bytes, options)?;
+    crate::encode_ascii_png(&ascii_data, "-", options).map_err(|e| JsError::new(&e.to_string()))
+}
+
+/// Converts encoded image bytes to a colored `<pre>` block.
+#[wasm_bindgen(js_name = toHtml)]
+pub fn to_html(bytes: &[u8], options: &Options) -> Result<String, JsError> {
+    Ok(html::render_pre(&convert(bytes, &options.options)?, &options.options))
+}